@@ -0,0 +1,11 @@
+// Loopback recipe of the self-test: every VeriComm input pin is echoed on the output pin with the
+// same index (see loopback_cons.xml), combinationally so the echo is sampled within the same cycle.
+// Build it with the FDE toolchain into loopback_dc_bit.bit next to this file, the recipe is only listed once it exists.
+module loopback (
+    input  wire [53:0] in_data,
+    output wire [53:0] out_data
+);
+
+assign out_data = in_data;
+
+endmodule
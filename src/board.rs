@@ -0,0 +1,476 @@
+/*
+ * Filename: board.rs
+ * Desciprtion: Board profiles (pin map, chip, peripherals & frame layout of an FDE board variant),
+ * the built-in default is embedded in the binary and more can be added as boards/<name>.json
+ */
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::helper::smims_cfg::CfgSnapshot;
+use crate::ports::{self, frame, IOPort, PortMappings};
+use crate::utilities::clock;
+
+/// Folder (relative to the working directory) that is scanned for additional profiles.
+pub const BOARDS_DIR: &str = "boards";
+pub const DEFAULT_BOARD: &str = "fde";
+
+/// Pin map of the default board, so that the CLI works from any directory.
+pub const DEFAULT_PIN_MAP: &str = include_str!("../fde/VERICOMM_MAP.json");
+
+fn default_frame_words() -> usize {
+    frame::WORDS_PER_FRAME
+}
+
+/// Segments of a seven-segment digit, in the order of its pins.
+const SEGMENTS: [&str; 8] = ["a", "b", "c", "d", "e", "f", "g", "dp"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PeripheralKind {
+    Led,
+    Switch,
+    Button,
+    SevenSegment,
+    Clock,
+    Reset,
+}
+
+impl fmt::Display for PeripheralKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            PeripheralKind::Led => write!(f, "LED"),
+            PeripheralKind::Switch => write!(f, "switch"),
+            PeripheralKind::Button => write!(f, "button"),
+            PeripheralKind::SevenSegment => write!(f, "7-segment digit"),
+            PeripheralKind::Clock => write!(f, "clock"),
+            PeripheralKind::Reset => write!(f, "reset"),
+        }
+    }
+}
+
+impl PeripheralKind {
+    /// Whether the pins have to be VeriComm inputs (`Some(true)`) or outputs (`Some(false)`) of the design.
+    /// Clocks are not VeriComm IO & a reset line can be either.
+    fn input(&self) -> Option<bool> {
+        match *self {
+            PeripheralKind::Switch | PeripheralKind::Button => Some(true),
+            PeripheralKind::Led | PeripheralKind::SevenSegment => Some(false),
+            PeripheralKind::Clock | PeripheralKind::Reset => None,
+        }
+    }
+}
+
+/// Something on the board a pin is wired to.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Peripheral {
+    pub name: String,
+    pub kind: PeripheralKind,
+    /// One pin per bit (LSB first), the segments a-g & dp of a seven-segment digit
+    pub pins: Vec<String>,
+    #[serde(default)]
+    pub description: String,
+    /// Frequency of a clock source, if known
+    #[serde(default)]
+    pub frequency_hz: Option<u64>,
+    /// LEDs, buttons & reset lines that are active when low
+    #[serde(default)]
+    pub active_low: bool,
+}
+
+impl Peripheral {
+    /// The label of one of its pins: the name for a single pin, `name[i]` or the segment (`name.a`) otherwise.
+    pub fn label(&self, pin: &str) -> Option<String> {
+        let bit = self.pins.iter().position(|candidate| candidate == pin)?;
+        Some(match self.kind {
+            _ if self.pins.len() == 1 => self.name.clone(),
+            PeripheralKind::SevenSegment => format!("{}.{}", self.name, SEGMENTS.get(bit).unwrap_or(&"?")),
+            _ => format!("{}[{}]", self.name, bit),
+        })
+    }
+
+    /// E.g. `the on-board oscillator, 30.000 MHz` or `reset button (active low)`.
+    pub fn describe(&self) -> String {
+        let mut text = if self.description.is_empty() { format!("{} {}", self.kind, self.name) } else { self.description.clone() };
+        if let Some(hz) = self.frequency_hz {
+            text = format!("{}, {}", text, clock::format_frequency(hz as f64));
+        }
+        if self.active_low {
+            text = format!("{} (active low)", text);
+        }
+        text
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BoardProfile {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    /// FPGA on the board
+    #[serde(default)]
+    pub chip: String,
+    /// Package pin -> bit of the input (tx) frame
+    pub input: HashMap<String, i32>,
+    /// Package pin -> bit of the output (rx) frame, 1-based
+    pub output: HashMap<String, i32>,
+    /// LEDs, switches, buttons, seven-segment digits, clock sources & reset lines, only what is known of the board
+    #[serde(default)]
+    pub peripherals: Vec<Peripheral>,
+    /// 16-bit FIFO words per frame (cycle)
+    #[serde(default = "default_frame_words")]
+    pub frame_words: usize,
+    /// Configuration space fields (see `CfgField::key`) that identify the board, e.g. `{"smims_version": 2}`
+    #[serde(default)]
+    pub detect: BTreeMap<String, u16>,
+}
+
+impl BoardProfile {
+    /// The profile of the FDE board the CLI was written for. Only its clock pins are known (from the recipes),
+    /// the LEDs, switches, buttons, seven-segment digits & reset lines are not documented in this repository
+    /// and are left out rather than guessed, they can be described in a profile under `boards/`.
+    pub fn fde() -> Self {
+        let mappings = ports::fde_parse_ports().expect("embedded pin map is valid JSON");
+        BoardProfile {
+            name: DEFAULT_BOARD.to_string(),
+            description: "FDE board (built-in, clock pins only)".to_string(),
+            chip: "FDP3P7".to_string(),
+            input: mappings.input,
+            output: mappings.output,
+            // The clocks of the recipes, their frequencies are not documented
+            peripherals: vec![
+                Peripheral {
+                    name: "vericomm_clk".to_string(),
+                    kind: PeripheralKind::Clock,
+                    pins: vec!["P77".to_string()],
+                    description: "the VeriComm clock, clk in the recipes".to_string(),
+                    frequency_hz: None,
+                    active_low: false,
+                },
+                Peripheral {
+                    name: "osc".to_string(),
+                    kind: PeripheralKind::Clock,
+                    pins: vec!["P185".to_string()],
+                    description: "the on-board oscillator, clk_30M in afifo_test".to_string(),
+                    frequency_hz: None,
+                    active_low: false,
+                },
+            ],
+            frame_words: frame::WORDS_PER_FRAME,
+            detect: BTreeMap::new(),
+        }
+    }
+
+    pub fn mappings(&self) -> PortMappings {
+        PortMappings { input: self.input.clone(), output: self.output.clone() }
+    }
+
+    /// Checks that the profile fits the frame layout the CLI encodes (4 words, 64 pins per direction).
+    pub fn validate(&self) -> Result<(), String> {
+        if self.frame_words != frame::WORDS_PER_FRAME {
+            return Err(format!(
+                "board {}: frames of {} words are not supported (only {})",
+                self.name,
+                self.frame_words,
+                frame::WORDS_PER_FRAME
+            ));
+        }
+        let pins = 16 * self.frame_words as i32;
+        let input = self.input.iter().find(|(_, bit)| !(0..pins).contains(*bit));
+        let output = self.output.iter().find(|(_, bit)| !(1..=pins).contains(*bit));
+        if let Some((pin, bit)) = input.or(output) {
+            return Err(format!("board {}: {} is mapped to bit {}, outside of the frame", self.name, pin, bit));
+        }
+
+        let mut wired: BTreeMap<&str, &str> = BTreeMap::new();
+        for peripheral in self.peripherals.iter() {
+            for pin in peripheral.pins.iter() {
+                if let Some(other) = wired.insert(pin, &peripheral.name) {
+                    return Err(format!("board {}: {} is wired to both {} and {}", self.name, pin, other, peripheral.name));
+                }
+                let is_input = self.input.contains_key(pin);
+                let is_output = self.output.contains_key(pin);
+                let fits = match peripheral.kind {
+                    PeripheralKind::Clock => !is_input && !is_output,
+                    kind => match kind.input() {
+                        Some(true) => is_input,
+                        Some(false) => is_output,
+                        None => true,
+                    },
+                };
+                if !fits {
+                    return Err(format!(
+                        "board {}: {} {} is on {}, which is not a{} pin",
+                        self.name,
+                        peripheral.kind,
+                        peripheral.name,
+                        pin,
+                        match peripheral.kind {
+                            PeripheralKind::Clock => " clock (non VeriComm)",
+                            kind if kind.input() == Some(true) => "n input",
+                            _ => "n output",
+                        }
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// The peripheral a pin is wired to & the label of the pin.
+    pub fn peripheral(&self, pin: &str) -> Option<(&Peripheral, String)> {
+        self.peripherals.iter().find_map(|peripheral| peripheral.label(pin).map(|label| (peripheral, label)))
+    }
+
+    /// The clock source on a pin.
+    pub fn clock(&self, pin: &str) -> Option<&Peripheral> {
+        self.peripheral(pin).map(|(peripheral, _)| peripheral).filter(|peripheral| peripheral.kind == PeripheralKind::Clock)
+    }
+
+    /// The peripheral(s) an IO port is wired to, e.g. `leds` for a bus on all LEDs, `sw[0], sw[3]` otherwise.
+    pub fn label(&self, port: &IOPort) -> Option<String> {
+        let wired: Vec<(&Peripheral, String)> = port.ports.iter().filter_map(|pin| self.peripheral(pin.pin_name())).collect();
+        let (first, _) = wired.first()?;
+        if wired.len() > 1 && wired.len() == first.pins.len() && wired.iter().all(|(peripheral, _)| peripheral.name == first.name) {
+            return Some(first.name.clone());
+        }
+        let mut labels: Vec<String> = wired.into_iter().map(|(_, label)| label).collect();
+        labels.dedup();
+        Some(labels.join(", "))
+    }
+
+    /// Labels the IO ports with the peripherals they are wired to.
+    pub fn label_ports(&self, io: &mut [IOPort]) {
+        for port in io.iter_mut() {
+            port.peripheral = self.label(port);
+        }
+    }
+
+    /// Whether the configuration space of a device matches the `detect` fields (never for an empty `detect`).
+    pub fn matches(&self, snapshot: &CfgSnapshot) -> bool {
+        !self.detect.is_empty() && self.detect.iter().all(|(key, value)| snapshot.fields.get(key) == Some(value))
+    }
+}
+
+/// How the active profile was chosen, a choice is only replaced by one of the same or a higher priority.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Selection {
+    Default,
+    /// From the configuration space of a mounted device
+    Detected,
+    /// From the meta file of the loaded project
+    Project,
+    /// With `board use`
+    User,
+}
+
+impl fmt::Display for Selection {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Selection::Default => write!(f, "default"),
+            Selection::Detected => write!(f, "detected"),
+            Selection::Project => write!(f, "project"),
+            Selection::User => write!(f, "board use"),
+        }
+    }
+}
+
+/// The known board profiles & the active one.
+pub struct BoardRegistry {
+    profiles: Vec<BoardProfile>,
+    active: usize,
+    selection: Selection,
+    /// Last profile detected from a device, to fall back to when a project choice no longer applies
+    detected: Option<usize>,
+}
+
+impl BoardRegistry {
+    /// The built-in profile plus the valid ones of `dir`, returning what could not be loaded.
+    pub fn load(dir: &str) -> (Self, Vec<String>) {
+        let mut registry =
+            BoardRegistry { profiles: vec![BoardProfile::fde()], active: 0, selection: Selection::Default, detected: None };
+        let mut errors = Vec::new();
+
+        let mut files: Vec<_> = fs::read_dir(Path::new(dir))
+            .map(|entries| entries.flatten().map(|entry| entry.path()).collect())
+            .unwrap_or_default();
+        files.retain(|path| path.extension().is_some_and(|extension| extension == "json"));
+        files.sort();
+
+        for path in files {
+            let profile = fs::read_to_string(&path)
+                .map_err(|e| format!("failed to read {}: {}", path.display(), e))
+                .and_then(|json| {
+                    serde_json::from_str::<BoardProfile>(&json).map_err(|e| format!("invalid {}: {}", path.display(), e))
+                })
+                .and_then(|profile| profile.validate().map(|_| profile));
+            match profile {
+                Ok(profile) if registry.find(&profile.name).is_some() => {
+                    errors.push(format!("{}: board {} is already defined", path.display(), profile.name))
+                }
+                Ok(profile) => registry.profiles.push(profile),
+                Err(e) => errors.push(e),
+            }
+        }
+        (registry, errors)
+    }
+
+    pub fn profiles(&self) -> &[BoardProfile] {
+        &self.profiles
+    }
+
+    pub fn active(&self) -> &BoardProfile {
+        &self.profiles[self.active]
+    }
+
+    pub fn selection(&self) -> Selection {
+        self.selection
+    }
+
+    fn find(&self, name: &str) -> Option<usize> {
+        self.profiles.iter().position(|profile| profile.name.eq_ignore_ascii_case(name))
+    }
+
+    /// Makes a profile active, unless one was chosen with a higher priority. Returns whether it is active.
+    pub fn select(&mut self, name: &str, selection: Selection) -> Result<bool, String> {
+        let index = self.find(name).ok_or(format!("unknown board \"{}\", see `board list`", name))?;
+        if selection < self.selection {
+            return Ok(false);
+        }
+        self.active = index;
+        self.selection = selection;
+        Ok(true)
+    }
+
+    /// Activates the first profile whose `detect` fields match the configuration space of a device.
+    pub fn detect(&mut self, snapshot: &CfgSnapshot) -> Option<&BoardProfile> {
+        let index = self.profiles.iter().position(|profile| profile.matches(snapshot))?;
+        self.detected = Some(index);
+        let name = self.profiles[index].name.clone();
+        match self.select(&name, Selection::Detected) {
+            Ok(true) => Some(self.active()),
+            _ => None,
+        }
+    }
+
+    /// Drops the choice of the previous project (for a project that names no board), going back to the
+    /// detected or default profile. Returns the profile that is active now if the choice was dropped.
+    pub fn clear_project(&mut self) -> Option<&BoardProfile> {
+        if self.selection != Selection::Project {
+            return None;
+        }
+        (self.active, self.selection) = match self.detected {
+            Some(index) => (index, Selection::Detected),
+            None => (0, Selection::Default),
+        };
+        Some(self.active())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_profile() {
+        let profile = BoardProfile::fde();
+        assert!(profile.validate().is_ok());
+        assert_eq!(profile.input.get("P151"), Some(&0));
+        assert_eq!(profile.output.get("P7"), Some(&1));
+        assert_eq!(profile.clock("P77").unwrap().describe(), "the VeriComm clock, clk in the recipes");
+        assert!(profile.peripherals.iter().all(|peripheral| peripheral.kind == PeripheralKind::Clock));
+        assert!(profile.clock("P151").is_none());
+    }
+
+    #[test]
+    fn test_peripherals() {
+        // A made up board on the FDE pin map, not the wiring of the FDE board (which is not documented)
+        let mut profile = BoardProfile::fde();
+        profile.name = "example".to_string();
+        let json = r#"[
+            {"name": "sw", "kind": "switch", "pins": ["P151", "P148"]},
+            {"name": "digit", "kind": "seven_segment", "pins": ["P7", "P6", "P5", "P4", "P9", "P8", "P16", "P15"]},
+            {"name": "rst_n", "kind": "reset", "pins": ["P18"], "description": "reset button", "active_low": true},
+            {"name": "osc", "kind": "clock", "pins": ["P185"], "frequency_hz": 25000000}
+        ]"#;
+        profile.peripherals = serde_json::from_str(json).unwrap();
+        assert!(profile.validate().is_ok());
+        assert_eq!(profile.peripheral("P148").unwrap().1, "sw[1]");
+        assert_eq!(profile.peripheral("P16").unwrap().1, "digit.g");
+        assert_eq!(profile.peripheral("P18").unwrap().0.describe(), "reset button (active low)");
+        assert_eq!(profile.clock("P185").unwrap().describe(), "clock osc, 25.000 MHz");
+
+        // name_display on that board: lcd_db is on all the pins of the digit, rst on one switch
+        let mut io = ports::recipe_io("name_display");
+        profile.label_ports(&mut io);
+        let label = |name: &str| io.iter().find(|port| port.io_name == name).unwrap().peripheral.clone();
+        assert_eq!(label("lcd_db"), Some("digit".to_string()));
+        assert_eq!(label("rst"), Some("sw[0]".to_string()));
+        assert_eq!(label("lcd_rst"), Some("rst_n".to_string()));
+        assert_eq!(label("lcd_en"), None);
+
+        // A switch has to be an input of the design
+        profile.peripherals[0].pins.push("P11".to_string());
+        assert!(profile.validate().unwrap_err().contains("not an input pin"));
+    }
+
+    #[test]
+    fn test_registry() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut variant = BoardProfile::fde();
+        variant.name = "fde-v2".to_string();
+        variant.detect = BTreeMap::from([("smims_version".to_string(), 7)]);
+        fs::write(dir.path().join("v2.json"), serde_json::to_string(&variant).unwrap()).unwrap();
+        variant.frame_words = 8;
+        fs::write(dir.path().join("wide.json"), serde_json::to_string(&variant).unwrap()).unwrap();
+        fs::write(dir.path().join("broken.json"), "{").unwrap();
+
+        let (mut registry, errors) = BoardRegistry::load(dir.path().to_str().unwrap());
+        assert_eq!(registry.profiles().len(), 2);
+        assert_eq!(errors.len(), 2);
+        assert_eq!(registry.active().name, DEFAULT_BOARD);
+
+        // Detected from the cfg of a device
+        let mut snapshot = CfgSnapshot { source: "test".to_string(), fields: BTreeMap::new() };
+        assert!(registry.detect(&snapshot).is_none());
+        snapshot.fields.insert("smims_version".to_string(), 7);
+        assert_eq!(registry.detect(&snapshot).unwrap().name, "fde-v2");
+
+        // A project (or the user) overrides detection, not the other way around
+        assert_eq!(registry.select("FDE", Selection::User), Ok(true));
+        assert!(registry.detect(&snapshot).is_none());
+        assert_eq!(registry.select("fde-v2", Selection::Project), Ok(false));
+        assert_eq!(registry.active().name, DEFAULT_BOARD);
+        assert!(registry.select("missing", Selection::User).is_err());
+    }
+
+    #[test]
+    fn test_clear_project() {
+        let mut registry = BoardRegistry::load("missing").0;
+        let mut variant = BoardProfile::fde();
+        variant.name = "fde-v2".to_string();
+        variant.detect = BTreeMap::from([("smims_version".to_string(), 7)]);
+        registry.profiles.push(variant);
+
+        // Back to the default for the next project without a board
+        assert_eq!(registry.select("fde-v2", Selection::Project), Ok(true));
+        assert_eq!(registry.clear_project().unwrap().name, DEFAULT_BOARD);
+        assert_eq!(registry.selection(), Selection::Default);
+
+        // Or to the board that was detected, which can be detected again later
+        let snapshot = CfgSnapshot { source: "test".to_string(), fields: BTreeMap::from([("smims_version".to_string(), 7)]) };
+        assert!(registry.detect(&snapshot).is_some());
+        assert_eq!(registry.select("fde", Selection::Project), Ok(true));
+        assert_eq!(registry.clear_project().unwrap().name, "fde-v2");
+        assert_eq!(registry.selection(), Selection::Detected);
+
+        // Nothing to drop for a choice that is not the project's
+        assert_eq!(registry.select("fde", Selection::User), Ok(true));
+        assert!(registry.clear_project().is_none());
+        assert_eq!(registry.active().name, DEFAULT_BOARD);
+    }
+}
@@ -1,5 +1,5 @@
 use std::collections::HashMap;
-use std::sync::{Arc, Mutex, MutexGuard, atomic::{AtomicBool, Ordering}};
+use std::sync::{Arc, Mutex, atomic::{AtomicBool, Ordering}};
 use std::thread;
 use std::time::Duration;
 type ThreadHandle = Arc<Mutex<HashMap<u64, (thread::JoinHandle<()>, Arc<AtomicBool>)>>>;
@@ -11,23 +11,23 @@ use owo_colors::OwoColorize;
 use tabled::Table;
 use tabled::settings::{Style, Alignment, object::Columns};
 
-use libusb1_sys as libusb_ffi;
-
-use crate::ports::{self, table};
+use crate::ports::{self, format, frame::{self, Frame}, table};
 use crate::vlfd::{
     device_handler,
     ProgramHandler,
     helper::*,
-    structs::{UsbDevice, UsbHandle}
 };
-use crate::helper::{bitstream, cli_commands, constraints, smims_cfg};
+use crate::board::{self, BoardRegistry, PeripheralKind, Selection};
+use crate::device_manager::{BoardSession, DeviceManager};
+use crate::helper::{bitstream, check_cons, cli_commands, constraints, smims_cfg};
 use crate::manager::{self, FileEntry, ScanResult};
+use crate::utilities::{capture, clock, decode, flash, golden, lcd, monitor, selftest, step, stimulus, testbench, transfer, trigger, vcd, wave};
 
 pub fn handle_command(command: &str, app_context: &mut AppContext) -> Result<bool> {
     match command.to_lowercase().as_str() {
         "help" => {
             cli_commands::show_help();
-            return Ok(true);
+            Ok(true)
         }
         "lsusb" => {
             print_usb_devices()?;
@@ -46,81 +46,59 @@ pub fn handle_command(command: &str, app_context: &mut AppContext) -> Result<boo
                 );
             }
             
-            app_context.fde_devices = fde_devices;
+            app_context.devices.set_devices(fde_devices);
 
             println!("{}", "Mount a FDE device by calling `mount i`".yellow());
             
             Ok(true)
         }
         command if command.starts_with("mount ") => {
-            // Dump the configuration space for a specific device
-            if let Some(id) = command.split_whitespace().nth(1) {
-                if !id.is_ascii() {
-                    println!("invalid input");
+            // Open a USB handle to a discovered device, it is owned by the device manager until `unmount`
+            let id = match app_context.devices.parse_id(command.split_whitespace().nth(1).unwrap_or_default()) {
+                Ok(id) => id,
+                Err(e) => {
+                    println!("{}", e.to_string().red());
                     return Ok(true);
                 }
-                let id: u32 = match id.parse() {
-                    Ok(id) => id,
-                    Err(error) => {
-                        println!("{}", error);
-                        return Ok(true)
-                    },
-                };
-
-                // id contains the index of the device descriptor stored in our app state that we want to mount
-                // Some checks
-                if id as usize > app_context.fde_devices.len() - 1 {
-                    println!("id {} is out of bounds", id);
-                    return Ok(true);
+            };
+            match app_context.devices.mount(id) {
+                Ok(session) => {
+                    println!("{} device {}", "Mounted".green(), id);
+                    // Pick the board profile from the configuration space, unless a project or the user chose one
+                    if app_context.boards.selection() <= Selection::Detected {
+                        match read_cfg_snapshot(id, session) {
+                            Ok(Some(snapshot)) => {
+                                let previous = app_context.boards.active().name.clone();
+                                if let Some(profile) = app_context.boards.detect(&snapshot) {
+                                    println!("Detected board {}", profile.name.yellow());
+                                    // The IO of a loaded project was mapped with the pins of the previous board
+                                    if profile.name != previous {
+                                        remap_project_io(app_context);
+                                    }
+                                }
+                            }
+                            Ok(None) => {}
+                            Err(e) => println!("{} {}", "Could not read the configuration space:".yellow(), e),
+                        }
+                    }
                 }
-
-                let fde_usb_device = &app_context.fde_devices[id as usize];
-
-                let usb_handle = get_usb_handle(
-                    fde_usb_device.bus,
-                    fde_usb_device.address,
-                    fde_usb_device.id_vendor,
-                    fde_usb_device.id_product
-                )?;
-
-                let mut handles = app_context.fde_handles.lock().unwrap();
-                handles.insert(fde_usb_device.clone(), UsbHandle{ handle:usb_handle, context: libusb_get_context() });
-                // app_context.fde_handles.
+                Err(e) => println!("{}", e.to_string().red()),
             }
 
             Ok(true)
         }
         command if command.starts_with("unmount ") => {
-            // Dump the configuration space for a specific device
-            if let Some(id) = command.split_whitespace().nth(1) {
-                if !id.is_ascii() {
-                    println!("invalid input");
+            // Close the USB handle of a mounted device
+            let id = match app_context.devices.parse_id(command.split_whitespace().nth(1).unwrap_or_default()) {
+                Ok(id) => id,
+                Err(e) => {
+                    println!("{}", e.to_string().red());
                     return Ok(true);
                 }
-                let id: u32 = match id.parse() {
-                    Ok(id) => id,
-                    Err(error) => {
-                        println!("{}", error);
-                        return Ok(true)
-                    },
-                };
-
-                // id contains the index of the device descriptor stored in our app state that we want to mount
-                // Some checks
-                if id as usize > app_context.fde_devices.len() - 1 {
-                    println!("id {} is out of bounds", id);
-                    return Ok(true);
-                }
-
-                let fde_usb_device = &app_context.fde_devices[id as usize];
-                let mut handles = app_context.fde_handles.lock().unwrap();
-                let fde_handle = &handles[fde_usb_device];
-
-                unsafe {
-                    libusb_ffi::libusb_close(fde_handle.handle);
-                    // Optionally set the handle to null to prevent double-closing.
-                    handles.remove(fde_usb_device);
-                }
+            };
+            match app_context.devices.unmount(id) {
+                Ok(_) => println!("{} device {}", "Unmounted".green(), id),
+                Err(e) => println!("{}", e.to_string().red()),
             }
 
             Ok(true)
@@ -142,13 +120,13 @@ pub fn handle_command(command: &str, app_context: &mut AppContext) -> Result<boo
             println!("Listing {} projects/recipies:", "discovered".yellow());
             let manager_results = &app_context.project_manager;
 
-            if manager_results.projects.len() == 0 {
+            if manager_results.projects.is_empty() {
                 println!("{}", "No projects found".red());
             } else {
                 println!("{}", "Projects".bold().green());
                 println!("{}", serde_json::to_string_pretty(&manager_results.projects).unwrap());
             }
-            if manager_results.recipes.len() == 0 {
+            if manager_results.recipes.is_empty() {
                 println!("{}", "No recipies found".red());
             } else {
                 println!("{}", "Recipes".bold().green());
@@ -196,6 +174,24 @@ pub fn handle_command(command: &str, app_context: &mut AppContext) -> Result<boo
                 }
                 constraints_loader.print_ports();
 
+                // The meta file can name the board profile the project is for
+                let meta = manager::read_meta(entry);
+                if let Some(name) = meta.as_ref().ok().and_then(|meta| meta.board.as_deref()) {
+                    match app_context.boards.select(name, Selection::Project) {
+                        Ok(true) => println!("Using board {}", name.yellow()),
+                        Ok(false) => println!(
+                            "{} the project is for board {}, keeping {} (chosen with `board use`)",
+                            "Warning:".yellow(),
+                            name,
+                            app_context.boards.active().name
+                        ),
+                        Err(e) => println!("{} {}", "Warning:".yellow(), e),
+                    }
+                } else if let Some(profile) = app_context.boards.clear_project() {
+                    println!("The project names no board, using board {}", profile.name.yellow());
+                }
+                check_project_cons(constraints_loader.get_ports(), app_context.boards.active());
+
                 // Read & load bitstream file
                 println!("Reading bitsream...");
                 let mut bitstream_loader = bitstream::ProgramDataReader::new(entry.dc_bit.to_str().unwrap());
@@ -204,17 +200,12 @@ pub fn handle_command(command: &str, app_context: &mut AppContext) -> Result<boo
                 }
                 bitstream_loader.preview_prorgam_data();
 
-                let port_mappings = ports::fde_parse_ports().unwrap();
-                let constraints = constraints_loader.get_ports();
-                let mut port_vec: Vec<ports::Port> = Vec::new();
-
-                for constraint in constraints.iter() {
-                    let new_port = ports::new_port(constraint.clone(), port_mappings.clone());
-                    println!("{:?}", new_port);
-                    port_vec.push(new_port);
+                if let Err(e) = &meta {
+                    println!("{}", e.yellow());
                 }
+                let io = map_project_io(constraints_loader.get_ports(), meta.as_ref().ok(), app_context.boards.active());
 
-                app_context.io = Some(ports::group_ports(&port_vec, port_mappings));
+                app_context.io = Some(io);
                 app_context.current_project = Some(entry.clone());
             }
             Ok(true)
@@ -225,37 +216,8 @@ pub fn handle_command(command: &str, app_context: &mut AppContext) -> Result<boo
         // ================================================================================================
         command if command.starts_with("fde_dump_conf ") => {
             // Dump the configuration space for a specific device
-            if app_context.fde_devices.len() == 0 {
-                println!("{}", "No fde_devices found.".yellow());
-                return Ok(true);
-            }
-            if let Some(id) = command.split_whitespace().nth(1) {
-                if !id.is_ascii() {
-                    println!("invalid input");
-                    return Ok(true);
-                }
-                let id: u32 = match id.parse() {
-                    Ok(id) => id,
-                    Err(error) => {
-                        println!("{}", error);
-                        return Ok(true)
-                    },
-                };
-
-                if id as usize > app_context.fde_devices.len() - 1 {
-                    println!("id {} is out of bounds", id);
-                    return Ok(true);
-                }
-
-                let fde_usb_device = &app_context.fde_devices[id as usize];
-                let handles = app_context.fde_handles.lock().unwrap();
-
-                if handles.len() == 0 {
-                    println!("{}", "No fde_handles found.".yellow());
-                    return Ok(true);
-                }
-
-                let fde_handle = &handles[fde_usb_device];
+            if let Some((_, session)) = select_board(&app_context.devices, command.split_whitespace().nth(1)) {
+                let fde_handle = session.handle();
 
                 let mut device_handler = device_handler::DeviceHandler::new(fde_handle);
                 if let Err(e) = device_handler.open() {
@@ -280,23 +242,262 @@ pub fn handle_command(command: &str, app_context: &mut AppContext) -> Result<boo
 
             Ok(true)
         }
+        command if command.starts_with("clock ") => {
+            // Show the design clock delays of a specific device
+            let args: Vec<&str> = command.split_whitespace().collect();
+            if args.len() != 2 {
+                println!("usage: clock <dev>");
+                return Ok(true);
+            }
+
+            let fde_handle = match select_board(&app_context.devices, Some(args[1])) {
+                Some((_, session)) => session.handle(),
+                None => return Ok(true),
+            };
+
+            let mut device_handler = device_handler::DeviceHandler::new(fde_handle);
+            device_handler.open().map_err(|e| anyhow::anyhow!("{}", e))?;
+            device_handler.init().map_err(|e| anyhow::anyhow!("{}", e))?;
+
+            let setting = clock::ClockSetting::from_cfg(&device_handler.cfg);
+            println!("Design clock: high delay {:#06x}, low delay {:#06x}", setting.high_delay, setting.low_delay);
+            Ok(true)
+        }
+        lowered if lowered.starts_with("conf ") => {
+            // Save/compare configuration snapshots, arguments are taken from the raw command
+            // so that file names keep their case
+            let args: Vec<&str> = command.split_whitespace().collect();
+            match (args.get(1).map(|s| s.to_lowercase()).as_deref(), args.len()) {
+                (Some("save"), 4) => {
+                    let (id, session) = match select_board(&app_context.devices, Some(args[2])) {
+                        Some(selected) => selected,
+                        None => return Ok(true),
+                    };
+                    if let Some(snapshot) = read_cfg_snapshot(id, session)? {
+                        match snapshot.save(args[3]) {
+                            Ok(_) => println!("{} configuration of device {} to {}", "Saved".green(), id, args[3].yellow()),
+                            Err(e) => println!("{}", e.red()),
+                        }
+                    }
+                }
+                (Some("diff"), 4) => {
+                    let mut snapshots = Vec::new();
+                    for source in &args[2..4] {
+                        // A number refers to a discovered device, anything else to a snapshot file
+                        let snapshot = if source.parse::<usize>().is_ok() {
+                            match select_board(&app_context.devices, Some(source)) {
+                                Some((id, session)) => read_cfg_snapshot(id, session)?,
+                                None => None,
+                            }
+                        } else {
+                            smims_cfg::CfgSnapshot::load(source)
+                                .map_err(|e| println!("{}", e.red()))
+                                .ok()
+                        };
+                        match snapshot {
+                            Some(snapshot) => snapshots.push(snapshot),
+                            None => return Ok(true),
+                        }
+                    }
+
+                    let diff = smims_cfg::CfgDiffTable::from_snapshots(&snapshots[0], &snapshots[1]);
+                    println!("a: {}\nb: {}", snapshots[0].source, snapshots[1].source);
+                    if diff.is_empty() {
+                        println!("{}", "Configurations are identical".green());
+                    } else {
+                        println!("{} field(s) {}", diff.len(), "differ".red());
+                        let mut table = Table::new(diff);
+                        table.with(Style::modern());
+                        table.modify(Columns::first(), Alignment::right());
+                        println!("{}", table);
+                    }
+                }
+                _ => {
+                    println!("usage: conf save <dev> <file> | conf diff <dev|file> <dev|file>");
+                }
+            }
+
+            Ok(true)
+        }
+        lowered if lowered.starts_with("flash ") => {
+            // Show the onboard flash of a specific device (the vlfd driver has no flash access, so it is read-only)
+            let args: Vec<&str> = command.split_whitespace().collect();
+            if args.get(1).map(|s| s.to_lowercase()).as_deref() != Some("info") || args.len() != 3 {
+                println!("usage: flash info <dev>");
+                return Ok(true);
+            }
+
+            let fde_handle = match select_board(&app_context.devices, Some(args[2])) {
+                Some((_, session)) => session.handle(),
+                None => return Ok(true),
+            };
+
+            let mut device_handler = device_handler::DeviceHandler::new(fde_handle);
+            device_handler.open().map_err(|e| anyhow::anyhow!("{}", e))?;
+            device_handler.init().map_err(|e| anyhow::anyhow!("{}", e))?;
+            let geometry = flash::FlashGeometry::from_cfg(&device_handler.cfg);
+
+            println!(
+                "Flash: {} blocks of {} words ({} words per cluster), {} words in total",
+                geometry.total_blocks, geometry.block_size, geometry.cluster_size, geometry.capacity()
+            );
+            let region = flash::FlashRegion::from_cfg(&device_handler.cfg);
+            match region.words(&geometry) {
+                Ok(words) => println!(
+                    "Region: block {} cluster {} ..= block {} cluster {} ({} words)",
+                    region.begin_block, region.begin_cluster, region.end_block, region.end_cluster, words
+                ),
+                Err(e) => println!("{} {}", "Region:".yellow(), e),
+            }
+
+            Ok(true)
+        }
+        command if command.starts_with("selftest ") => {
+            // Exercise the whole stack of a specific device and print a pass/fail report
+            let (id, session) = match select_board(&app_context.devices, command.split_whitespace().nth(1)) {
+                Some(selected) => selected,
+                None => return Ok(true),
+            };
+            let fde_handle = session.handle();
+
+            let mut report = selftest::SelfTestReport::new();
+            let mut device_handler = device_handler::DeviceHandler::new(fde_handle);
+
+            let mut ok = report.check("Device opens", device_handler.open()
+                .map(|_| "USB handle opened".to_string())
+                .map_err(|e| e.to_string()));
+            ok = ok && report.check("Configuration read", device_handler.init()
+                .map(|_| "cfg space read".to_string())
+                .map_err(|e| e.to_string()));
+            if ok {
+                let cfg = &device_handler.cfg;
+                report.check("Version fields", selftest::check_versions(
+                    smims_cfg::CfgField::SmimsVersion.read(cfg),
+                    smims_cfg::CfgField::SmimsMajorVersion.read(cfg),
+                    smims_cfg::CfgField::SmimsSubVersion.read(cfg),
+                    smims_cfg::CfgField::SmimsSubsubVersion.read(cfg),
+                ));
+                ok = report.check("VeriComm ability", if smims_cfg::CfgField::VericommAbility.read(cfg) == 1 {
+                    Ok("supported".to_string())
+                } else {
+                    Err("the engine does not report VeriComm support".to_string())
+                });
+            }
+            ok = ok && report.check("Engine reset", device_handler.engine_reset()
+                .map(|_| "engine reset".to_string())
+                .map_err(|e| e.to_string()));
+
+            // Programming & IO need the loopback recipe
+            let loopback = manager::find_file_entry_by_folder(&app_context.project_manager.recipes, selftest::LOOPBACK_RECIPE);
+            if ok && loopback.is_none() {
+                let detail = format!(
+                    "recipe \"{0}\" not found (no bitstream), build recipes/{0}/{0}.v into {0}_dc_bit.bit",
+                    selftest::LOOPBACK_RECIPE
+                );
+                report.record("Program loopback", selftest::Outcome::Skip, detail.clone());
+                report.record("IO round trips", selftest::Outcome::Skip, detail);
+                ok = false;
+            }
+            if ok {
+                let bitstream_file = loopback.unwrap().dc_bit.clone();
+                let mut program_handler = ProgramHandler::new(fde_handle);
+                let programmed = program_handler.open_device()
+                    .and_then(|_| program_handler.program(&bitstream_file))
+                    .map(|_| format!("{}", bitstream_file.display()))
+                    .map_err(|e| e.to_string());
+                let _ = program_handler.close_device();
+                ok = report.check("Program loopback", programmed);
+                session.set_programmed(ok);
+
+                ok = ok && report.check("Is programmed", device_handler.open()
+                    .and_then(|_| device_handler.init())
+                    .map_err(|e| e.to_string())
+                    .and_then(|_| match smims_cfg::CfgField::IsProgrammed.read(&device_handler.cfg) {
+                        1 => Ok("is_programmed went true".to_string()),
+                        _ => Err("is_programmed is still false".to_string()),
+                    }));
+            }
+            if ok {
+                ok = report.check("IO open", device_handler.io_open()
+                    .map(|_| "VeriComm IO opened".to_string())
+                    .map_err(|e| e.to_string()));
+                session.set_io_open(ok);
+                if ok {
+                    let fifo_size = smims_cfg::CfgField::FifoSize.read(&device_handler.cfg) as usize;
+                    for size in selftest::buffer_sizes(fifo_size) {
+                        let mut tx_buffer = selftest::loopback_pattern(size);
+                        let mut rx_buffer: Vec<u16> = vec![0u16; size];
+                        let result = device_handler.io_write_read_data(&mut tx_buffer, &mut rx_buffer)
+                            .map_err(|e| e.to_string())
+                            .and_then(|_| selftest::compare_loopback(&tx_buffer, &rx_buffer));
+                        report.check(&format!("IO round trip ({} words)", size), result);
+                    }
+                    let _ = device_handler.io_close();
+                    session.set_io_open(false);
+                }
+            }
+
+            let mut table = Table::new(report.steps());
+            table.with(Style::modern());
+            println!("{}", table);
+            if report.passed() && report.skipped() > 0 {
+                println!("Self-test of device {} {} ({} steps skipped)", id, "PASSED".green(), report.skipped().to_string().yellow());
+            } else if report.passed() {
+                println!("Self-test of device {} {}", id, "PASSED".green());
+            } else {
+                println!("Self-test of device {} {}", id, "FAILED".red());
+            }
+
+            Ok(true)
+        }
         "fde_handles" => {
-            let handles: MutexGuard<_> = app_context.fde_handles.lock().unwrap();
-            if handles.is_empty() {
+            let sessions = app_context.devices.sessions();
+            if sessions.is_empty() {
                 println!("No fde_handles found.");
-                return Ok(true)
+                Ok(true)
             } else {
                 println!("Mounted USB handles:");
-                for (i, (usb_device, usb_handle)) in handles.iter().enumerate() {
+                for (i, session) in sessions {
+                    let (usb_device, usb_handle) = (&session.device, session.handle());
                     println!(
-                        "{i} | Device (Bus: {}, Address: {}, VID: {:#04x}, PID: {:#04x}) => Handle: {:?}, Context: {:?}",
-                        usb_device.bus, usb_device.address, usb_device.id_vendor, usb_device.id_product, usb_handle.handle, usb_handle.context
+                        "{i} | Device (Bus: {}, Address: {}, VID: {:#04x}, PID: {:#04x}) => Handle: {:?}, Context: {:?}, IO open: {}, Programmed: {}",
+                        usb_device.bus, usb_device.address, usb_device.id_vendor, usb_device.id_product, usb_handle.handle, usb_handle.context,
+                        session.is_io_open(), session.is_programmed()
                     );
                 }
-                return Ok(true)
+                Ok(true)
             }
         }
 
+        command if command.starts_with("usb ") => {
+            // Show or change the retry/recovery settings of a mounted board
+            let args: Vec<&str> = command.split_whitespace().collect();
+            if let Some((id, session)) = select_board(&app_context.devices, args.get(1).copied()) {
+                match args.len() {
+                    2 => {}
+                    4 => {
+                        let mut settings = session.settings();
+                        if let Err(e) = settings.set(args[2], args[3]) {
+                            println!("{}", e.red());
+                            return Ok(true);
+                        }
+                        session.set_settings(settings);
+                    }
+                    _ => {
+                        println!("{}", "Usage: usb <dev> [deadline|retries|backoff|reprogram <value>]".red());
+                        return Ok(true);
+                    }
+                }
+                let settings = session.settings();
+                println!(
+                    "Device {}: {} retries within {} ms, backoff {} ms (doubling), reprogram on recovery: {}",
+                    id, settings.retries, settings.retry_deadline_ms, settings.backoff_ms,
+                    if settings.reprogram { "on" } else { "off" }
+                );
+            }
+            Ok(true)
+        }
+
         // ================================================================================================
         // ========================================= FDE BOARD ============================================
         // ================================================================================================
@@ -311,47 +512,661 @@ pub fn handle_command(command: &str, app_context: &mut AppContext) -> Result<boo
                     usb_device.serial_number
             )   ;
             }
-            return Ok(true);
+            Ok(true)
         }
 
         command if command.starts_with("arm ") => {
-            // Arm's a FDE device that is already has its USB connection initiated
-            
-            if let Some(id) = command.split_whitespace().nth(1) {
-                println!("{}", id);
+            // Arm's a FDE device that is already has its USB connection initiated,
+            // commands without a <dev> argument (e.g. `step`) use the armed device
+            if let Some((id, _)) = select_board(&app_context.devices, command.split_whitespace().nth(1)) {
+                app_context.armed = Some(id);
+                app_context.cycle = 0;
+                println!("{} device {}", "Armed".green(), id);
             }
 
             Ok(true)
         }
 
-        command if command.starts_with("reset ") => {
-            if let Some(id) = command.split_whitespace().nth(1) {
-                if !id.is_ascii() {
-                    println!("invalid input");
+        command if command == "step" || command.starts_with("step ") => {
+            // Send the current inputs to the armed device frame by frame, stepping the design clock if it is gated
+            let Some(armed) = app_context.armed else {
+                println!("{}", "No device armed, call `arm <dev>` first".red());
+                return Ok(true);
+            };
+            let Some((_, session)) = select_board(&app_context.devices, Some(&armed.to_string())) else {
+                return Ok(true);
+            };
+
+            let arg = command.split_whitespace().nth(1).unwrap_or("1");
+            let cycles: usize = match arg.parse() {
+                Ok(cycles) if cycles > 0 => cycles,
+                _ => {
+                    println!("usage: step [cycles]");
                     return Ok(true);
                 }
-                let id: u32 = match id.parse() {
-                    Ok(id) => id,
-                    Err(error) => {
-                        println!("{}", error);
-                        return Ok(true)
-                    },
+            };
+            let Some(ref mut current_io) = app_context.io else {
+                println!("{}", "No project loaded".red());
+                return Ok(true);
+            };
+
+            let bitstream = app_context.current_project.as_ref().map(|project| project.dc_bit.clone());
+            let Some((frames, single_step)) = drive_inputs(session, bitstream, current_io, cycles, true) else {
+                return Ok(true);
+            };
+            for (sample, frame) in frames.into_iter().enumerate() {
+                step::update_outputs(current_io, frame);
+                // A free running clock is not advanced by the frames, they are only samples
+                if single_step {
+                    app_context.cycle += 1;
+                    println!("Cycle {}", app_context.cycle.to_string().yellow());
+                } else {
+                    println!("Sample {}", (sample + 1).to_string().yellow());
+                }
+                print_io_table(current_io);
+            }
+            Ok(true)
+        }
+
+        lowered if lowered.starts_with("monitor ") => {
+            // Poll the outputs with the current inputs until `q`, inputs can be changed with `set` meanwhile
+            let args: Vec<&str> = command.split_whitespace().skip(1).collect();
+            let Some((_, session)) = select_board(&app_context.devices, args.first().copied()) else {
+                return Ok(true);
+            };
+            let Some(ref mut current_io) = app_context.io else {
+                println!("{}", "No project loaded".red());
+                return Ok(true);
+            };
+            let mut monitor = match monitor::Monitor::new(current_io, &args[1..]) {
+                Ok(monitor) => monitor,
+                Err(e) => {
+                    println!("{}", e.red());
+                    return Ok(true);
+                }
+            };
+            let bitstream = app_context.current_project.as_ref().map(|project| project.dc_bit.clone());
+            let mut io_session = match transfer::IoSession::open(session, bitstream) {
+                Ok(io_session) => io_session,
+                Err(e) => {
+                    println!("{}", format!("ERROR {}", e).red());
+                    return Ok(true);
+                }
+            };
+
+            let single_step = step::is_single_step(&io_session.handler().cfg);
+
+            // Lines typed while monitoring are read on another thread so that polling does not wait for them
+            let (line_tx, line_rx) = std::sync::mpsc::channel::<String>();
+            let reader = thread::spawn(move || {
+                // Sending fails once the monitor has stopped, any line then ends the thread
+                for line in std::io::stdin().lines().map_while(Result::ok) {
+                    let quit = matches!(line.trim(), "q" | "quit");
+                    if line_tx.send(line).is_err() || quit {
+                        break;
+                    }
+                }
+            });
+            println!("Monitoring, type `set <port> <value>` to change an input or `q` to stop");
+
+            // Lines printed since the top of the last table, to redraw it in place
+            let mut drawn = 0;
+            let mut status = String::new();
+            'monitor: loop {
+                while let Ok(line) = line_rx.try_recv() {
+                    drawn += 1;
+                    let words: Vec<&str> = line.split_whitespace().collect();
+                    status = match words.as_slice() {
+                        ["q" | "quit"] => break 'monitor,
+                        [] => String::new(),
+                        ["set", name, value] => match ports::find_port_mut(current_io, name) {
+                            Some(port) => match ports::parse_port_value(value).and_then(|value| port.set_value(value)) {
+                                Ok(_) => format!("{} = {}", port.io_name, port.formatted()),
+                                Err(e) => e,
+                            },
+                            None => format!("Unknown port \"{}\"", name),
+                        },
+                        _ => "usage: set <port> <value>, or q to stop".to_string(),
+                    };
+                }
+
+                let mut tx_buffer = frame::encode(&[Frame::from_io(current_io)]);
+                let mut rx_buffer = vec![0u16; tx_buffer.len()];
+                if let Err(e) = io_session.write_read(&mut tx_buffer, &mut rx_buffer) {
+                    println!("{}", format!("ERROR {} (after {} samples)", e, monitor.samples).red());
+                    println!("Press Enter to return to the prompt");
+                    break;
+                }
+                for frame in frame::decode(&rx_buffer) {
+                    step::update_outputs(current_io, frame);
+                    monitor.observe(current_io);
+                    if single_step {
+                        app_context.cycle += 1;
+                    }
+                }
+
+                if monitor.due() {
+                    let mut lines = monitor.render(current_io);
+                    lines.push(status.clone());
+                    // Back to the top of the last table, then clear to the end of the screen
+                    if drawn > 0 {
+                        print!("\x1b[{}A", drawn);
+                    }
+                    print!("\x1b[J");
+                    println!("{}", lines.join("\n"));
+                    drawn = lines.len();
+                }
+            }
+            // The reader is blocked on stdin until the next line, which it can no longer send & so it exits
+            drop(line_rx);
+            let _ = reader.join();
+            println!("Stopped monitoring after {} samples", monitor.samples);
+            Ok(true)
+        }
+
+        lowered if lowered.starts_with("capture ") => {
+            // Record a run of the loaded project, the VCD file name is taken from the raw command
+            let args: Vec<&str> = command.split_whitespace().collect();
+            let vcd_file = match (args.len(), args.get(3).map(|arg| arg.to_lowercase()).as_deref()) {
+                (3, _) => None,
+                (5, Some("--vcd")) => Some(args[4]),
+                _ => {
+                    println!("usage: capture <dev> <cycles> [--vcd out.vcd]");
+                    return Ok(true);
+                }
+            };
+            let cycles: usize = match args[2].parse() {
+                Ok(cycles) if cycles > 0 => cycles,
+                _ => {
+                    println!("{}", format!("invalid number of cycles \"{}\"", args[2]).red());
+                    return Ok(true);
+                }
+            };
+            let Some((_, session)) = select_board(&app_context.devices, Some(args[1])) else {
+                return Ok(true);
+            };
+            let Some(ref mut current_io) = app_context.io else {
+                println!("{}", "No project loaded".red());
+                return Ok(true);
+            };
+
+            let bitstream = app_context.current_project.as_ref().map(|project| project.dc_bit.clone());
+            let Some((frames, _)) = drive_inputs(session, bitstream, current_io, cycles, false) else {
+                return Ok(true);
+            };
+
+            let mut run = capture::Capture::new(current_io);
+            let tx = Frame::from_io(current_io);
+            for &rx in frames.iter() {
+                run.push(tx, rx);
+            }
+            if let Some(&last) = frames.last() {
+                step::update_outputs(current_io, last);
+            }
+            println!("{} {} cycles", "Captured".green(), run.len());
+
+            if let Some(vcd_file) = vcd_file {
+                match vcd::save_vcd(&run, vcd_file) {
+                    Ok(_) => println!("{} waveform to {}", "Saved".green(), vcd_file.yellow()),
+                    Err(e) => println!("{}", e.red()),
+                }
+            }
+            app_context.last_capture = Some(run);
+            app_context.wave.from = 0;
+            Ok(true)
+        }
+
+        lowered if lowered == "trigger" || lowered.starts_with("trigger ") => {
+            // Condition of `capture_trigger`, port names are taken from the raw command
+            let condition = command.trim()["trigger".len()..].trim();
+            match condition {
+                "" => match app_context.trigger.as_ref() {
+                    Some(condition) => println!("Trigger: {}", condition.yellow()),
+                    None => println!("No trigger set, e.g. `trigger o_rdata==0x3x && rise(o_wfull)`"),
+                },
+                "clear" => {
+                    app_context.trigger = None;
+                    println!("Trigger cleared");
+                }
+                condition => {
+                    let Some(ref current_io) = app_context.io else {
+                        println!("{}", "No project loaded".red());
+                        return Ok(true);
+                    };
+                    match trigger::parse(condition, current_io) {
+                        Ok(_) => {
+                            app_context.trigger = Some(condition.to_string());
+                            println!("Trigger: {}", condition.yellow());
+                        }
+                        Err(e) => println!("{}", e.red()),
+                    }
+                }
+            }
+            Ok(true)
+        }
+
+        lowered if lowered.starts_with("capture_trigger ") => {
+            // Stream cycles until the trigger fires and keep the cycles around it as the last capture
+            let args: Vec<&str> = command.split_whitespace().collect();
+            let usage = "usage: capture_trigger <dev> [--pre n] [--post n] [--max cycles] [--vcd out.vcd]";
+            let (mut pre, mut post, mut max, mut vcd_file) = (16usize, 16usize, 1_000_000u64, None);
+            let mut options = args[2..].iter();
+            while let Some(option) = options.next() {
+                let value = options.next();
+                let parsed = match (option.to_lowercase().as_str(), value) {
+                    ("--pre", Some(n)) => n.parse().map(|n| pre = n).is_ok(),
+                    ("--post", Some(n)) => n.parse().map(|n| post = n).is_ok(),
+                    ("--max", Some(n)) => n.parse().map(|n| max = n).is_ok(),
+                    ("--vcd", Some(file)) => {
+                        vcd_file = Some(*file);
+                        true
+                    }
+                    _ => false,
                 };
-    
-                if id as usize > app_context.fde_devices.len() - 1 {
-                    println!("id {} is out of bounds", id);
+                if !parsed {
+                    println!("{}", usage);
                     return Ok(true);
                 }
-    
-                let fde_usb_device = &app_context.fde_devices[id as usize];
-                let handles = app_context.fde_handles.lock().unwrap();
-    
-                if handles.len() == 0 {
-                    println!("{}", "No fde_handles found.".yellow());
+            }
+            let Some(ref condition) = app_context.trigger else {
+                println!("{}", "No trigger set, call `trigger <condition>` first".red());
+                return Ok(true);
+            };
+            let Some((_, session)) = select_board(&app_context.devices, args.get(1).copied()) else {
+                return Ok(true);
+            };
+            let Some(ref mut current_io) = app_context.io else {
+                println!("{}", "No project loaded".red());
+                return Ok(true);
+            };
+            let condition = match trigger::parse(condition, current_io) {
+                Ok(condition) => condition,
+                Err(e) => {
+                    println!("{} {}", "The trigger does not fit the loaded project:".red(), e);
                     return Ok(true);
                 }
-    
-                let fde_handle = &handles[fde_usb_device];
+            };
+
+            let bitstream = app_context.current_project.as_ref().map(|project| project.dc_bit.clone());
+            let mut io_session = match transfer::IoSession::open(session, bitstream) {
+                Ok(io_session) => io_session,
+                Err(e) => {
+                    println!("{}", format!("ERROR {}", e).red());
+                    return Ok(true);
+                }
+            };
+            let fifo_size = smims_cfg::CfgField::FifoSize.read(&io_session.handler().cfg) as usize;
+            let frames_per_transfer = (fifo_size / frame::WORDS_PER_FRAME).max(1);
+
+            println!("Waiting for {} (at most {} cycles)...", app_context.trigger.as_deref().unwrap_or_default().yellow(), max);
+            let tx = Frame::from_io(current_io);
+            let mut engine = trigger::TriggerCapture::new(condition, current_io, pre, post);
+            let mut last = None;
+            'stream: while engine.cycles < max {
+                let mut tx_buffer = frame::encode(&vec![tx; frames_per_transfer]);
+                let mut rx_buffer = vec![0u16; tx_buffer.len()];
+                if let Err(e) = io_session.write_read(&mut tx_buffer, &mut rx_buffer) {
+                    println!("{}", format!("ERROR {} (after {} cycles)", e, engine.cycles).red());
+                    return Ok(true);
+                }
+                for rx in frame::decode(&rx_buffer) {
+                    last = Some(rx);
+                    if engine.push(tx, rx) || engine.cycles >= max {
+                        break 'stream;
+                    }
+                }
+            }
+            if let Some(last) = last {
+                step::update_outputs(current_io, last);
+            }
+
+            let (fired, cycles, complete) = (engine.fired, engine.cycles, engine.done());
+            let Some((run, offset)) = engine.into_capture() else {
+                println!("{} the trigger did not fire in {} cycles", "Timeout:".yellow(), cycles);
+                return Ok(true);
+            };
+            println!(
+                "{} at cycle {}, captured {} cycles (the trigger is cycle {} of the capture)",
+                "Triggered".green(),
+                fired.unwrap_or_default(),
+                run.len(),
+                offset
+            );
+            if !complete {
+                println!("{} stopped after {} cycles, before all post-trigger cycles", "Warning:".yellow(), cycles);
+            }
+
+            if let Some(vcd_file) = vcd_file {
+                match vcd::save_vcd(&run, vcd_file) {
+                    Ok(_) => println!("{} waveform to {}", "Saved".green(), vcd_file.yellow()),
+                    Err(e) => println!("{}", e.red()),
+                }
+            }
+            app_context.last_capture = Some(run);
+            app_context.wave.from = offset.saturating_sub(4);
+            Ok(true)
+        }
+
+        lowered if lowered.starts_with("stimulus ") => {
+            // A stimulus that could not be run counts as a failed check, so that a script fails
+            app_context.failed_checks += run_stimulus(command, app_context).unwrap_or(1);
+            Ok(true)
+        }
+
+        lowered if lowered.starts_with("run ") => {
+            // A testbench that could not be run counts as a failed check, so that a script fails
+            app_context.failed_checks += run_testbench(command, app_context).unwrap_or(1);
+            Ok(true)
+        }
+
+        lowered if lowered.starts_with("decode ") => {
+            // Decode UART, SPI or I2C from the signals of the last capture, port names are taken from the raw command
+            let args: Vec<&str> = command.split_whitespace().collect();
+            let usage = "usage: decode uart <port> <cycles/bit> [--bits n] [--parity even|odd] [--csv file]\n       \
+                         decode spi <sclk> <mosi> [--miso port] [--cs port] [--mode 0-3] [--bits n] [--lsb] [--csv file]\n       \
+                         decode i2c <scl> <sda> [--csv file]";
+            let Some(ref run) = app_context.last_capture else {
+                println!("{}", "No capture, call `capture` first".red());
+                return Ok(true);
+            };
+            let protocol = args.get(1).map(|protocol| protocol.to_lowercase()).unwrap_or_default();
+            let positional = match protocol.as_str() {
+                "uart" | "spi" | "i2c" => 2,
+                _ => {
+                    println!("{}", usage);
+                    return Ok(true);
+                }
+            };
+            if args.len() < 2 + positional {
+                println!("{}", usage);
+                return Ok(true);
+            }
+
+            // Options of all protocols, each takes a value except --lsb
+            let mut options: HashMap<String, &str> = HashMap::new();
+            let mut rest = args[2 + positional..].iter();
+            while let Some(option) = rest.next() {
+                let option = option.to_lowercase();
+                match (option.as_str(), option.as_str() == "--lsb") {
+                    (_, true) => {
+                        options.insert(option, "");
+                    }
+                    ("--bits" | "--parity" | "--csv" | "--miso" | "--cs" | "--mode", false) => match rest.next() {
+                        Some(value) => {
+                            options.insert(option, value);
+                        }
+                        None => {
+                            println!("{}", usage);
+                            return Ok(true);
+                        }
+                    },
+                    _ => {
+                        println!("{}", usage);
+                        return Ok(true);
+                    }
+                }
+            }
+            let number = |option: &str, default: usize| -> std::result::Result<usize, String> {
+                options.get(option).map_or(Ok(default), |value| {
+                    value.parse().map_err(|_| format!("invalid {} \"{}\"", option, value))
+                })
+            };
+            let signal = |name: &str| decode::signal(run, name);
+
+            let decoded = match protocol.as_str() {
+                "uart" => (|| {
+                    let line = signal(args[2])?;
+                    let cycles_per_bit = args[3].parse().map_err(|_| format!("invalid number of cycles per bit \"{}\"", args[3]))?;
+                    let parity = match options.get("--parity").map(|parity| parity.to_lowercase()).as_deref() {
+                        None | Some("none") => None,
+                        Some("even") => Some(decode::Parity::Even),
+                        Some("odd") => Some(decode::Parity::Odd),
+                        Some(parity) => return Err(format!("invalid parity \"{}\" (none, even or odd)", parity)),
+                    };
+                    let config = decode::UartConfig { data_bits: number("--bits", 8)?, parity, ..decode::UartConfig::new(cycles_per_bit) };
+                    decode::uart(&line, &config)
+                })(),
+                "spi" => (|| {
+                    let (sclk, mosi) = (signal(args[2])?, signal(args[3])?);
+                    let miso = options.get("--miso").map(|name| signal(name)).transpose()?;
+                    let cs = options.get("--cs").map(|name| signal(name)).transpose()?;
+                    let mode = number("--mode", 0)?;
+                    let mut config = decode::SpiConfig::mode(mode.min(u8::MAX as usize) as u8)?;
+                    config.word_bits = number("--bits", 8)?.clamp(1, 64);
+                    config.lsb_first = options.contains_key("--lsb");
+                    let signals = decode::SpiSignals { sclk: &sclk, mosi: &mosi, miso: miso.as_deref(), cs: cs.as_deref() };
+                    Ok(decode::spi(&signals, &config))
+                })(),
+                _ => signal(args[2]).and_then(|scl| Ok(decode::i2c(&scl, &signal(args[3])?))),
+            };
+            let transactions = match decoded {
+                Ok(transactions) => transactions,
+                Err(e) => {
+                    println!("{}", e.red());
+                    return Ok(true);
+                }
+            };
+
+            if transactions.is_empty() {
+                println!("Nothing decoded in {} cycles", run.len());
+            } else {
+                let mut table = Table::new(&transactions);
+                table.with(Style::modern());
+                println!("{}", table);
+                println!("{} transaction(s)", transactions.len());
+            }
+            if let Some(csv_file) = options.get("--csv") {
+                match decode::save_csv(&transactions, csv_file) {
+                    Ok(_) => println!("{} transactions to {}", "Saved".green(), csv_file.yellow()),
+                    Err(e) => println!("{}", e.red()),
+                }
+            }
+            Ok(true)
+        }
+
+        lowered if lowered == "lcd" || lowered.starts_with("lcd ") => {
+            // Draw the HD44780 character LCD driven by the last capture, mapped in the meta file of the project
+            let show_log = match lowered.split_whitespace().nth(1) {
+                None => false,
+                Some("--log") => true,
+                Some(_) => {
+                    println!("usage: lcd [--log]");
+                    return Ok(true);
+                }
+            };
+            let Some(ref run) = app_context.last_capture else {
+                println!("{}", "No capture, call `capture` first".red());
+                return Ok(true);
+            };
+            let mapping = match app_context.current_project.as_ref().map(manager::read_meta) {
+                Some(Ok(meta)) => meta.lcd.unwrap_or_default(),
+                Some(Err(e)) => {
+                    println!("{} {}, using the default LCD ports", "Warning:".yellow(), e);
+                    lcd::LcdMapping::default()
+                }
+                None => lcd::LcdMapping::default(),
+            };
+
+            let (display, events) = match lcd::decode(run, &mapping) {
+                Ok(decoded) => decoded,
+                Err(e) => {
+                    println!("{}", e.red());
+                    return Ok(true);
+                }
+            };
+            // Without RS the writes cannot be run, so they are listed instead of the display
+            if (show_log || mapping.rs.is_none()) && !events.is_empty() {
+                let mut table = Table::new(&events);
+                table.with(Style::modern());
+                println!("{}", table);
+            }
+            println!("{} write(s) in {} cycles", events.len(), run.len());
+            if mapping.rs.is_none() {
+                println!("{} RS is not mapped (\"rs\" under \"lcd\" in the meta file), commands cannot be told from characters", "Note:".yellow());
+                return Ok(true);
+            }
+            for line in display.render(mapping.columns.max(1)) {
+                println!("{}", line);
+            }
+            if !display.display_on {
+                println!("The display is off");
+            }
+            Ok(true)
+        }
+
+        lowered if lowered == "wave" || lowered.starts_with("wave ") => {
+            // Terminal timing diagram of the last capture, the view is kept so that it can be scrolled & zoomed
+            let Some(ref last_capture) = app_context.last_capture else {
+                println!("{}", "Nothing captured yet, call `capture` or `stimulus` first".red());
+                return Ok(true);
+            };
+            let args: Vec<&str> = command.split_whitespace().skip(1).collect();
+            let width = wave::terminal_width();
+            let name_width = last_capture.ports().iter().map(|port| port.io_name.len()).max().unwrap_or(0);
+            let page = app_context.wave.visible_cycles(name_width, width);
+
+            let view = &mut app_context.wave;
+            match args.first().map(|arg| arg.to_lowercase()).as_deref() {
+                None => {}
+                Some(direction @ ("left" | "right")) => {
+                    let cycles: isize = match args.get(1).map(|n| n.parse()) {
+                        None => (page / 2).max(1) as isize,
+                        Some(Ok(cycles)) => cycles,
+                        Some(Err(_)) => {
+                            println!("usage: wave left|right [cycles]");
+                            return Ok(true);
+                        }
+                    };
+                    view.scroll(if direction == "left" { -cycles } else { cycles }, last_capture.len());
+                }
+                Some("zoom") => {
+                    view.zoom = match args.get(1).map(|zoom| zoom.to_lowercase()).as_deref() {
+                        Some("in") => view.zoom * 2,
+                        Some("out") => view.zoom / 2,
+                        Some(zoom) => zoom.parse().unwrap_or(view.zoom),
+                        None => wave::DEFAULT_ZOOM,
+                    }
+                    .clamp(1, wave::MAX_ZOOM);
+                }
+                Some(_) => {
+                    let mut new_view = wave::WaveView { zoom: view.zoom, ..wave::WaveView::default() };
+                    let mut options = args.iter();
+                    while let Some(arg) = options.next() {
+                        let number = |value: Option<&&str>| value.and_then(|value| value.parse::<usize>().ok());
+                        match arg.to_lowercase().as_str() {
+                            "--from" => new_view.from = number(options.next()).unwrap_or(0),
+                            "--to" => new_view.to = number(options.next()),
+                            "--zoom" => new_view.zoom = number(options.next()).unwrap_or(wave::DEFAULT_ZOOM).clamp(1, wave::MAX_ZOOM),
+                            _ => new_view.ports.push(arg.to_string()),
+                        }
+                    }
+                    *view = new_view;
+                }
+            }
+
+            match wave::render(last_capture, view, width) {
+                Ok(lines) => lines.iter().for_each(|line| println!("{}", line)),
+                Err(e) => println!("{}", e.red()),
+            }
+            Ok(true)
+        }
+
+        lowered if lowered.starts_with("set ") => {
+            // Change the value driven on an input port, port names are taken from the raw command
+            let args: Vec<&str> = command.split_whitespace().collect();
+            if args.len() != 3 {
+                println!("usage: set <port> <value>");
+                return Ok(true);
+            }
+            let Some(ref mut current_io) = app_context.io else {
+                println!("{}", "No project loaded".red());
+                return Ok(true);
+            };
+            let Some(port) = ports::find_port_mut(current_io, args[1]) else {
+                println!("Unknown port \"{}\"", args[1].red());
+                return Ok(true);
+            };
+
+            if let Err(e) = ports::parse_port_value(args[2]).and_then(|value| port.set_value(value)) {
+                println!("{}", e.red());
+                return Ok(true);
+            }
+            println!("{} = {}", port.io_name.yellow(), port.formatted());
+            println!("Input frame: {}", Frame::from_io(current_io));
+            Ok(true)
+        }
+
+        lowered if lowered == "format" || lowered.starts_with("format ") => {
+            // Display format of the IO ports, port names are taken from the raw command
+            let args: Vec<&str> = command.split_whitespace().collect();
+            let Some(ref mut current_io) = app_context.io else {
+                println!("{}", "No project loaded".red());
+                return Ok(true);
+            };
+            match args.len() {
+                1 => {
+                    for port in current_io.iter().filter(|port| !matches!(port.io_type, ports::IOType::DC)) {
+                        println!("\t{}: {}", port.io_name.yellow(), port.format);
+                    }
+                }
+                3 => {
+                    let Some(port) = ports::find_port_mut(current_io, args[1]) else {
+                        println!("Unknown port \"{}\"", args[1].red());
+                        return Ok(true);
+                    };
+                    if let Err(e) = args[2].parse().and_then(|format| port.set_format(format)) {
+                        println!("{}", e.red());
+                        return Ok(true);
+                    }
+                    println!("{} = {}", port.io_name.yellow(), port.formatted());
+
+                    // The last capture is shown with the new format too
+                    let recorded = app_context.last_capture.as_mut().and_then(|capture| ports::find_port_mut(&mut capture.io, &port.io_name));
+                    if let Some(recorded) = recorded {
+                        recorded.format = port.format;
+                    }
+                }
+                _ => println!("usage: format [<port> <dec|hex|bin|signed|ascii|level|fixed:n|sfixed:n>]"),
+            }
+            Ok(true)
+        }
+
+        command if command == "apply" || command.starts_with("apply ") => {
+            // Send the current input state to the armed device and print the decoded outputs
+            let cycles: usize = match command.split_whitespace().nth(1).unwrap_or("1").parse() {
+                Ok(cycles) if cycles > 0 => cycles,
+                _ => {
+                    println!("usage: apply [cycles]");
+                    return Ok(true);
+                }
+            };
+            let Some(armed) = app_context.armed else {
+                println!("{}", "No device armed, call `arm <dev>` first".red());
+                return Ok(true);
+            };
+            let Some((_, session)) = select_board(&app_context.devices, Some(&armed.to_string())) else {
+                return Ok(true);
+            };
+            let Some(ref mut current_io) = app_context.io else {
+                println!("{}", "No project loaded".red());
+                return Ok(true);
+            };
+
+            let bitstream = app_context.current_project.as_ref().map(|project| project.dc_bit.clone());
+            let Some((frames, _)) = drive_inputs(session, bitstream, current_io, cycles, false) else {
+                return Ok(true);
+            };
+            if let Some(&last) = frames.last() {
+                step::update_outputs(current_io, last);
+            }
+            println!("Applied {} for {} cycle(s):", Frame::from_io(current_io), cycles);
+            print_io_table(current_io);
+            Ok(true)
+        }
+
+        command if command.starts_with("reset ") => {
+            if let Some((_, session)) = select_board(&app_context.devices, command.split_whitespace().nth(1)) {
+                let fde_handle = session.handle();
     
                 let mut device_handler = device_handler::DeviceHandler::new(fde_handle);
                 if let Err(e) = device_handler.open() {
@@ -367,54 +1182,88 @@ pub fn handle_command(command: &str, app_context: &mut AppContext) -> Result<boo
                 }
             }
 
-            return Ok(true)
+            Ok(true)
         }
         
 
         
+        lowered if lowered == "board" || lowered.starts_with("board ") => {
+            let args: Vec<&str> = command.split_whitespace().skip(1).collect();
+            match args.as_slice() {
+                [] => {
+                    let profile = app_context.boards.active();
+                    println!(
+                        "Board {} ({}), chip {}, {} input & {} output pins",
+                        profile.name.yellow(),
+                        app_context.boards.selection(),
+                        profile.chip,
+                        profile.input.len(),
+                        profile.output.len()
+                    );
+                    if profile.peripherals.iter().all(|peripheral| peripheral.kind == PeripheralKind::Clock) {
+                        println!(
+                            "{} no LEDs, switches, buttons, 7-segment digits or reset lines are known for this board",
+                            "Note:".yellow()
+                        );
+                    }
+                    for peripheral in profile.peripherals.iter() {
+                        println!("\t{} {}: {}", peripheral.kind, peripheral.name, peripheral.pins.join(", "));
+                        if !peripheral.description.is_empty() || peripheral.frequency_hz.is_some() || peripheral.active_low {
+                            println!("\t\t{}", peripheral.describe());
+                        }
+                    }
+                }
+                ["list"] => {
+                    let active = &app_context.boards.active().name;
+                    for profile in app_context.boards.profiles() {
+                        let marker = if &profile.name == active { "*" } else { " " };
+                        println!("{} {:<12} {:<8} {}", marker, profile.name, profile.chip, profile.description);
+                    }
+                }
+                ["use", name] => {
+                    let previous = app_context.boards.active().name.clone();
+                    match app_context.boards.select(name, Selection::User) {
+                        Ok(_) => {
+                            println!("Using board {}", app_context.boards.active().name.yellow());
+                            if app_context.boards.active().name != previous {
+                                remap_project_io(app_context);
+                            }
+                        }
+                        Err(e) => println!("{}", e.red()),
+                    }
+                }
+                _ => println!("{}", "Usage: board [list|use <name>]".red()),
+            }
+            Ok(true)
+        }
+        "check_cons" => {
+            let Some(current_project) = app_context.current_project.as_ref() else {
+                println!("{}", "No project loaded".red());
+                return Ok(true);
+            };
+            let mut constraints_loader = constraints::ConstraintsReader::new(current_project.cons.to_str().unwrap());
+            if let Err(e) = constraints_loader.read() {
+                println!("{} {}", "Something went wrong while reading contraints (.xml) file".red(), e);
+                return Ok(true);
+            }
+            check_project_cons(constraints_loader.get_ports(), app_context.boards.active());
+            Ok(true)
+        }
         "reconfigure" => {
             println!("Reconfiguring constraints and bitstreams...");
             // Implement reconfigure functionality here
-            return Ok(true);
+            Ok(true)
         }
         command if command.starts_with("test ") => {
-            if let Some(id) = command.split_whitespace().nth(1) {
-                if !id.is_ascii() {
-                    println!("invalid input");
-                    return Ok(true);
-                }
-                let id: u32 = match id.parse() {
-                    Ok(id) => id,
-                    Err(error) => {
-                        println!("{}", error);
-                        return Ok(true)
-                    },
+            if let Some((_, session)) = select_board(&app_context.devices, command.split_whitespace().nth(1)) {
+                let bitstream = app_context.current_project.as_ref().map(|project| project.dc_bit.clone());
+                let mut io_session = match transfer::IoSession::open(session, bitstream) {
+                    Ok(io_session) => io_session,
+                    Err(e) => {
+                        println!("{}", format!("ERROR {}", e).red());
+                        return Ok(true);
+                    }
                 };
-    
-                if id as usize > app_context.fde_devices.len() - 1 {
-                    println!("id {} is out of bounds", id);
-                    return Ok(true);
-                }
-    
-                let fde_usb_device = &app_context.fde_devices[id as usize];
-                let handles = app_context.fde_handles.lock().unwrap();
-    
-                if handles.len() == 0 {
-                    println!("{}", "No fde_handles found.".yellow());
-                    return Ok(true);
-                }
-    
-                let fde_handle = &handles[fde_usb_device];
-                let mut device_handler = device_handler::DeviceHandler::new(fde_handle);
-                if let Err(e) = device_handler.open() {
-                    // error!("ERROR {}", e); return Ok(true);
-                }
-
-                let _ = device_handler.init();
-
-                if let Err(e) = device_handler.io_open() {
-                    println!("ERROR {e}");
-                }
 
                 // let mut tx_buffer: Vec<u16> = [
                 //     0x0,
@@ -524,99 +1373,449 @@ pub fn handle_command(command: &str, app_context: &mut AppContext) -> Result<boo
 
                 let mut rx_buffer: Vec<u16> = [0u16; 8*7 + 12].to_vec();
                 // let mut rx_buffer: Vec<u16> = [0u16; 4 * 4].to_vec();
-                let _ = device_handler.io_write_read_data(&mut tx_buffer, &mut rx_buffer);
-                for chunk in rx_buffer.chunks_exact_mut(4) {
-                    // Convert the current 4 elements into a u64
-                    chunk.reverse();
-                    let data: u64 = ports::u16_4_to_u64(chunk);
+                if let Err(e) = io_session.write_read(&mut tx_buffer, &mut rx_buffer) {
+                    println!("{}", format!("ERROR {}", e).red());
+                    return Ok(true);
+                }
+                for data in frame::decode(&rx_buffer) {
                     if let Some(ref mut current_io) = app_context.io {
                         // Update the value for each port
-                        for io in current_io.iter_mut() { io.update(data); }
+                        for io in current_io.iter_mut() { io.update(data.bits()); }
 
                         let mut table = Table::new(
                             table::IOPortsTable::from_io(current_io)
                         );
+                        table::hide_peripherals(&mut table, current_io);
                         table.with(Style::modern());
                         table.modify(Columns::first(), Alignment::right());
-                        println!("{}", table.to_string());
+                        println!("{}", table);
                     } else {
                         println!("No IO to update.");
                     }
                 }
-
-                let _ = device_handler.io_close();
             }
 
-            return Ok(true);
+            Ok(true)
         }
         command if command.starts_with("program ") => {
-            // Dump the configuration space for a specific device
-            if app_context.fde_devices.len() == 0 {
-                println!("{}", "No fde_devices found.".yellow());
-                return Ok(true);
-            }
-            if let Some(id) = command.split_whitespace().nth(1) {
-                let id: u32 = match id.parse() {
-                    Ok(id) => id,
-                    Err(error) => {
-                        println!("{}", error);
-                        return Ok(true)
-                    },
+            // Program the bitstream of the current project, retrying according to the `usb` settings
+            if let Some((_, session)) = select_board(&app_context.devices, command.split_whitespace().nth(1)) {
+                let Some(current_project) = app_context.current_project.as_ref() else {
+                    println!("{}", "No project loaded".red());
+                    return Ok(true);
                 };
 
-                if id as usize > app_context.fde_devices.len() - 1 {
-                    println!("id {} is out of bounds", id);
+                // Refuse constraints that do not fit the board, unless forced
+                let force = command.split_whitespace().nth(2) == Some("--force");
+                let mut constraints_loader = constraints::ConstraintsReader::new(current_project.cons.to_str().unwrap());
+                // Constraints that cannot be read cannot be checked, which counts as an error
+                let errors = match constraints_loader.read() {
+                    Ok(_) => check_project_cons(constraints_loader.get_ports(), app_context.boards.active()),
+                    Err(e) => {
+                        println!("{} {}", "Something went wrong while reading contraints (.xml) file".red(), e);
+                        1
+                    }
+                };
+                if errors > 0 && !force {
+                    println!("Not programming, fix the constraints or use `program <dev> --force`");
                     return Ok(true);
                 }
 
-                let fde_usb_device = &app_context.fde_devices[id as usize];
-                let handles = app_context.fde_handles.lock().unwrap();
-
-                if handles.len() == 0 {
-                    println!("{}", "No fde_handles found.".yellow());
-                    return Ok(true);
+                if let Err(e) = transfer::program(session, &current_project.dc_bit) {
+                    println!("{}", e.to_string().red());
                 }
-
-                let fde_handle = &handles[fde_usb_device];
-
-                let mut program_handler = ProgramHandler::new(fde_handle);
-                if let Err(e) = program_handler.open_device().or_else(|e| {
-                    println!("{}", e);
-                    program_handler.close_device()?;
-                    Err(e)
-                }) { return Ok(true) }
-                
-                if app_context.current_project.is_none() {
-                    println!("{}", "No project loaded".red());
-                    return Ok(true);
-                }
-
-                let current_project = app_context.current_project.clone();
-                let bitstream_file = current_project.unwrap().dc_bit;
-                if let Err(e) = program_handler
-                    .program(std::path::Path::new(&bitstream_file))
-                    .or_else(|e| {
-                        program_handler.close_device()?;
-                        Err(e)
-                    }) {  return Ok(true); }
-                
-                let _ = program_handler.close_device();
             }
-            return Ok(true);
+            Ok(true)
         }
         "quit" => {
             // Cleanup
             
             println!("Thanks for using my software...");
-            return Ok(false);
+            Ok(false)
         }
         _ => {
             println!("Unknown command: {}, try `help` for commands", command.red());
-            return Ok(true);
+            Ok(true)
+        }
+    }
+}
+
+/// Holds the current inputs for `cycles` cycles on a board and returns the sampled output frames
+/// (and whether the clock is gated), printing the reason and returning `None` when the transfer failed.
+fn drive_inputs(
+    session: &BoardSession,
+    bitstream: Option<std::path::PathBuf>,
+    io: &[ports::IOPort],
+    cycles: usize,
+    warn_free_running: bool,
+) -> Option<(Vec<Frame>, bool)> {
+    stream_frames(session, bitstream, &vec![Frame::from_io(io); cycles], warn_free_running)
+}
+
+/// Sends input frames to a board, in transfers of at most the size of its FIFO, and returns the
+/// sampled output frames (& whether the clock is gated), printing the reason and returning `None` on failure.
+fn stream_frames(
+    session: &BoardSession,
+    bitstream: Option<std::path::PathBuf>,
+    frames: &[Frame],
+    warn_free_running: bool,
+) -> Option<(Vec<Frame>, bool)> {
+    let mut io_session = match transfer::IoSession::open(session, bitstream) {
+        Ok(io_session) => io_session,
+        Err(e) => {
+            println!("{}", format!("ERROR {}", e).red());
+            return None;
+        }
+    };
+    let single_step = step::is_single_step(&io_session.handler().cfg);
+    if warn_free_running && !single_step {
+        println!("{}", "The clock is free running, outputs are sampled but not stepped (the vlfd driver cannot gate it)".yellow());
+    }
+
+    let fifo_size = smims_cfg::CfgField::FifoSize.read(&io_session.handler().cfg) as usize;
+    let frames_per_transfer = (fifo_size / frame::WORDS_PER_FRAME).max(1);
+
+    let mut sampled = Vec::with_capacity(frames.len());
+    for chunk in frames.chunks(frames_per_transfer) {
+        let mut tx_buffer = frame::encode(chunk);
+        let mut rx_buffer = vec![0u16; tx_buffer.len()];
+        if let Err(e) = io_session.write_read(&mut tx_buffer, &mut rx_buffer) {
+            println!("{}", format!("ERROR {} (after {} of {} cycles)", e, sampled.len(), frames.len()).red());
+            return None;
         }
+        sampled.extend(frame::decode(&rx_buffer));
     }
+    Some((sampled, single_step))
 }
 
+/// Drives the inputs of the armed device from a VCD file (`stimulus vcd <file> ...`, arguments are taken from
+/// the raw command) and checks the outputs against its output signals. Returns the number of failed checks,
+/// `None` (after printing the reason) when the stimulus could not be run.
+fn run_stimulus(command: &str, app_context: &mut AppContext) -> Option<usize> {
+    let args: Vec<&str> = command.split_whitespace().collect();
+    let usage = "usage: stimulus vcd <file> [--map sig=port]... [--clock sig] [--vcd out.vcd]";
+    if args.len() < 3 || !args[1].eq_ignore_ascii_case("vcd") {
+        println!("{}", usage);
+        return None;
+    }
+
+    let (mut maps, mut clock_signal, mut vcd_file) = (Vec::new(), None, None);
+    let mut options = args[3..].iter();
+    while let Some(option) = options.next() {
+        match (option.to_lowercase().as_str(), options.next()) {
+            ("--map", Some(map)) => match stimulus::parse_map(map) {
+                Ok(map) => maps.push(map),
+                Err(e) => {
+                    println!("{}", e.red());
+                    return None;
+                }
+            },
+            ("--clock", Some(signal)) => clock_signal = Some(*signal),
+            ("--vcd", Some(file)) => vcd_file = Some(*file),
+            _ => {
+                println!("{}", usage);
+                return None;
+            }
+        }
+    }
+
+    let Some(armed) = app_context.armed else {
+        println!("{}", "No device armed, call `arm <dev>` first".red());
+        return None;
+    };
+    let (_, session) = select_board(&app_context.devices, Some(&armed.to_string()))?;
+    let Some(ref mut current_io) = app_context.io else {
+        println!("{}", "No project loaded".red());
+        return None;
+    };
+
+    let parsed = std::fs::read_to_string(args[2])
+        .map_err(|e| format!("failed to read {}: {}", args[2], e))
+        .and_then(|text| vcd::parse_vcd(&text));
+    let stimulus_vcd = match parsed {
+        Ok(stimulus_vcd) => stimulus_vcd,
+        Err(e) => {
+            println!("{}", e.red());
+            return None;
+        }
+    };
+    let bindings = match stimulus::bind(&stimulus_vcd, current_io, &maps) {
+        Ok(bindings) => bindings,
+        Err(e) => {
+            println!("{}", e.red());
+            return None;
+        }
+    };
+    let clock_index = match clock_signal {
+        Some(name) => match stimulus_vcd.signals.iter().position(|s| s.name == name || s.reference == name) {
+            Some(index) => Some(index),
+            None => {
+                println!("Clock signal \"{}\" is not in the VCD file", name.red());
+                return None;
+            }
+        },
+        None => None,
+    };
+
+    for binding in bindings.iter() {
+        let bit = binding.bit.map(|bit| format!("[{}]", bit)).unwrap_or_default();
+        println!("\t{} -> {}{}", stimulus_vcd.signals[binding.signal].name, current_io[binding.port].io_name.yellow(), bit);
+    }
+    let inputs = stimulus::frames(&stimulus_vcd, current_io, &bindings, clock_index);
+    let golden_outputs = match stimulus::bind_outputs(&stimulus_vcd, current_io) {
+        Ok(bindings) => bindings,
+        Err(e) => {
+            println!("{}", e.red());
+            return None;
+        }
+    };
+    for binding in golden_outputs.iter() {
+        let bit = binding.bit.map(|bit| format!("[{}]", bit)).unwrap_or_default();
+        println!("\t{} == {}{}", stimulus_vcd.signals[binding.signal].name, current_io[binding.port].io_name.yellow(), bit);
+    }
+    if bindings.is_empty() || inputs.is_empty() {
+        println!("{}", "Nothing to drive, no signal matches an input port (see --map)".red());
+        return None;
+    }
+
+    let bitstream = app_context.current_project.as_ref().map(|project| project.dc_bit.clone());
+    let (outputs, _) = stream_frames(session, bitstream, &inputs, false)?;
+
+    let mut run = capture::Capture::new(current_io);
+    for (&tx, &rx) in inputs.iter().zip(outputs.iter()) {
+        run.push(tx, rx);
+    }
+    if let Some(&last) = outputs.last() {
+        step::update_outputs(current_io, last);
+    }
+    println!("{} {} cycles from {}", "Streamed".green(), run.len(), args[2].yellow());
+
+    // Output signals of the VCD file are the golden values, unknown bits are not checked
+    let mut failed = 0;
+    if !golden_outputs.is_empty() {
+        let expected = stimulus::expectations(&stimulus_vcd, &golden_outputs, clock_index);
+        let mismatches = golden::compare(current_io, &expected, &outputs);
+        print_mismatches(&mismatches);
+        if mismatches.is_empty() {
+            println!("{} checks {}", expected.len(), "PASSED".green());
+        } else {
+            println!("{} of {} checks {}", mismatches.len(), expected.len(), "FAILED".red());
+        }
+        failed = mismatches.len();
+    }
+
+    if let Some(vcd_file) = vcd_file {
+        match vcd::save_vcd(&run, vcd_file) {
+            Ok(_) => println!("{} waveform to {}", "Saved".green(), vcd_file.yellow()),
+            Err(e) => println!("{}", e.red()),
+        }
+    }
+    app_context.last_capture = Some(run);
+    app_context.wave.from = 0;
+    Some(failed)
+}
+
+/// Runs a testbench on the armed device (`run <file>`, the file name is taken from the raw command).
+/// Returns the number of failed checks, `None` (after printing the reason) when the testbench could not be run.
+fn run_testbench(command: &str, app_context: &mut AppContext) -> Option<usize> {
+    let Some(file) = command.split_whitespace().nth(1) else {
+        println!("usage: run <file>");
+        return None;
+    };
+    let Some(armed) = app_context.armed else {
+        println!("{}", "No device armed, call `arm <dev>` first".red());
+        return None;
+    };
+    let (_, session) = select_board(&app_context.devices, Some(&armed.to_string()))?;
+    let Some(ref mut current_io) = app_context.io else {
+        println!("{}", "No project loaded".red());
+        return None;
+    };
+
+    let text = match std::fs::read_to_string(file) {
+        Ok(text) => text,
+        Err(e) => {
+            println!("{}", format!("failed to read {}: {}", file, e).red());
+            return None;
+        }
+    };
+    let program = match testbench::parse(&text).and_then(|statements| testbench::compile(&statements, current_io)) {
+        Ok(program) => program,
+        Err(e) => {
+            println!("{}", format!("{}: {}", file, e).red());
+            return None;
+        }
+    };
+    if program.frames.is_empty() {
+        println!("{}", "Nothing to run, the testbench has no `tick`".red());
+        return None;
+    }
+
+    let bitstream = app_context.current_project.as_ref().map(|project| project.dc_bit.clone());
+    let (outputs, _) = stream_frames(session, bitstream, &program.frames, true)?;
+
+    let results = testbench::check(&program, current_io, &outputs);
+    let mut table = Table::new(testbench::report(&text, &program, &results, current_io));
+    table.with(Style::modern());
+    table.modify(Columns::first(), Alignment::right());
+    println!("{}", table);
+
+    let mut run = capture::Capture::new(current_io);
+    for (&tx, &rx) in program.frames.iter().zip(outputs.iter()) {
+        run.push(tx, rx);
+    }
+    if let Some(&last) = outputs.last() {
+        step::update_outputs(current_io, last);
+    }
+
+    let mismatches = testbench::mismatches(&program, current_io, &outputs);
+    print_mismatches(&mismatches);
+    if mismatches.is_empty() {
+        println!("{} {} cycles, {} checks {}", file.yellow(), run.len(), results.len(), "PASSED".green());
+    } else {
+        println!("{} {} cycles, {} of {} checks {}", file.yellow(), run.len(), mismatches.len(), results.len(), "FAILED".red());
+    }
+    app_context.last_capture = Some(run);
+    app_context.wave.from = 0;
+    Some(mismatches.len())
+}
+
+/// Maps the constraints of a project to IO ports with the pin map of the board, applying the declared
+/// bus ranges & display formats of the meta file.
+fn map_project_io(
+    constraints: &[ports::ConstraintPort],
+    meta: Option<&manager::ProjectMeta>,
+    board: &board::BoardProfile,
+) -> Vec<ports::IOPort> {
+    let port_mappings = board.mappings();
+    let mut port_vec: Vec<ports::Port> = Vec::new();
+
+    for constraint in constraints.iter() {
+        let new_port = ports::new_port(constraint.clone(), port_mappings.clone());
+        println!("{:?}", new_port);
+        port_vec.push(new_port);
+    }
+
+    let mut io = ports::group_ports(&port_vec, port_mappings);
+    board.label_ports(&mut io);
+
+    // Declared bus ranges & display formats of the ports from the meta file
+    if let Some(meta) = meta {
+        for e in ports::bus::apply_declarations(&mut io, &meta.buses) {
+            println!("{} {}", "Ignoring bus range:".yellow(), e);
+        }
+        for e in format::apply_formats(&mut io, &meta.formats) {
+            println!("{} {}", "Ignoring format:".yellow(), e);
+        }
+    }
+    for warning in ports::bus::bus_warnings(&io) {
+        println!("{} {}", "Warning:".yellow(), warning);
+    }
+    io
+}
+
+/// Maps the IO of the loaded project again after the active board changed, keeping the values & formats
+/// the ports were given since it was loaded.
+fn remap_project_io(app_context: &mut AppContext) {
+    let (Some(entry), Some(previous)) = (app_context.current_project.as_ref(), app_context.io.as_ref()) else {
+        return;
+    };
+    let mut constraints_loader = constraints::ConstraintsReader::new(entry.cons.to_str().unwrap());
+    if let Err(e) = constraints_loader.read() {
+        println!("{} {}", "Something went wrong while reading contraints (.xml) file".red(), e);
+        return;
+    }
+    let board = app_context.boards.active();
+    println!("Mapping the IO of the project with board {}...", board.name.yellow());
+    check_project_cons(constraints_loader.get_ports(), board);
+
+    let meta = manager::read_meta(entry).ok();
+    let mut io = map_project_io(constraints_loader.get_ports(), meta.as_ref(), board);
+    for port in io.iter_mut() {
+        if let Some(old) = previous.iter().find(|old| old.io_name == port.io_name) {
+            port.format = old.format;
+            port.data = old.data;
+        }
+    }
+    app_context.io = Some(io);
+}
+
+/// Validates constraints against the pin map of the board (`check_cons`), printing the findings.
+/// Returns the number of errors.
+fn check_project_cons(constraints: &[ports::ConstraintPort], board: &board::BoardProfile) -> usize {
+    let findings = check_cons::check_cons(constraints, board);
+    for finding in findings.iter() {
+        let severity = match finding.severity {
+            check_cons::Severity::Error => finding.severity.to_string().red().to_string(),
+            check_cons::Severity::Warning => finding.severity.to_string().yellow().to_string(),
+            check_cons::Severity::Info => finding.severity.to_string().cyan().to_string(),
+        };
+        println!("\t{} {}", severity, finding.message);
+    }
+
+    let errors = check_cons::count(&findings, check_cons::Severity::Error);
+    let warnings = check_cons::count(&findings, check_cons::Severity::Warning);
+    if errors > 0 || warnings > 0 {
+        println!("Constraints: {} error(s), {} warning(s)", errors.to_string().red(), warnings.to_string().yellow());
+    } else {
+        println!("Constraints: {}", "OK".green());
+    }
+    errors
+}
+
+/// Prints the failed checks of a run (cycle, port, expected, actual & the bits that differ).
+fn print_mismatches(mismatches: &[golden::Mismatch]) {
+    if mismatches.is_empty() {
+        return;
+    }
+    let mut table = Table::new(mismatches);
+    table.with(Style::modern());
+    table.modify(Columns::first(), Alignment::right());
+    println!("{}", table);
+}
+
+fn print_io_table(io: &[ports::IOPort]) {
+    let mut table = Table::new(table::IOPortsTable::from_io(io));
+    table::hide_peripherals(&mut table, io);
+    table.with(Style::modern());
+    table.modify(Columns::first(), Alignment::right());
+    println!("{}", table);
+}
+
+/// Resolves the `<dev>` argument of a command to a mounted board,
+/// printing the reason and returning `None` when it cannot be used.
+fn select_board<'a>(devices: &'a DeviceManager, id: Option<&str>) -> Option<(usize, &'a BoardSession)> {
+    let id = match id {
+        Some(id) => id,
+        None => {
+            println!("{}", "Missing device id".red());
+            return None;
+        }
+    };
+    match devices.select(id) {
+        Ok(selected) => Some(selected),
+        Err(e) => {
+            println!("{}", e.to_string().red());
+            None
+        }
+    }
+}
+
+/// Reads the configuration space of a mounted device into a snapshot.
+fn read_cfg_snapshot(id: usize, session: &BoardSession) -> Result<Option<smims_cfg::CfgSnapshot>> {
+    let fde_usb_device = &session.device;
+    let mut device_handler = device_handler::DeviceHandler::new(session.handle());
+    device_handler.open().map_err(|e| anyhow::anyhow!("{}", e))?;
+    device_handler.init().map_err(|e| anyhow::anyhow!("{}", e))?;
+
+    let source = format!(
+        "device {} (Bus {:03} Device {:03} Serial: {:08x})",
+        id, fde_usb_device.bus, fde_usb_device.address, fde_usb_device.serial_number
+    );
+    Ok(Some(smims_cfg::CfgSnapshot::from_cfg(&source, &device_handler.cfg)))
+}
+
+#[allow(dead_code)]
 pub fn spawn_thread(threads: &ThreadHandle) {
     // Generate a new unique thread ID using rand crate
     let thread_id = 0;
@@ -645,6 +1844,7 @@ pub fn spawn_thread(threads: &ThreadHandle) {
     println!("Spawned thread with ID: {}", thread_id);
 }
 
+#[allow(dead_code)]
 pub fn kill_thread(threads: &ThreadHandle, thread_id: u64) {
     let mut locked_threads = threads.lock().unwrap();
 
@@ -664,46 +1864,74 @@ pub struct AppContext {
     // Running threads: key is an unique thread id.
     // pub threads: ThreadHandle,
 
-    // List of detected USB devices.
-    pub fde_devices: Vec<UsbDevice>,
-    // /// Opened USB handles, keyed by (bus, address, vid, pid).
-    pub fde_handles: Arc<Mutex<HashMap<UsbDevice, UsbHandle>>>,
+    // Detected FDE boards & the sessions of the mounted ones.
+    pub devices: DeviceManager,
 
     // Project/recipe manager
     pub project_manager: ScanResult,
 
     pub current_project: Option<FileEntry>,
-    pub io: Option<Vec<ports::IOPort>>
+    pub io: Option<Vec<ports::IOPort>>,
+
+    // Device used by commands without a <dev> argument (`arm`) & cycles stepped on it
+    pub armed: Option<usize>,
+    pub cycle: u64,
+
+    // The last recorded run (`capture`), used by the waveform views & exports
+    pub last_capture: Option<capture::Capture>,
+    pub wave: wave::WaveView,
+
+    // Condition of `capture_trigger`, parsed against the IO of the project when used
+    pub trigger: Option<String>,
+
+    // Checks (`run`, `stimulus`) that failed or could not run, a script exits with a nonzero status if any did
+    pub failed_checks: usize,
+
+    // Board profiles (pin maps) & the one the IO is mapped with
+    pub boards: BoardRegistry,
+}
+
+impl AppContext {
+    pub fn new() -> Self {
+        let (boards, errors) = BoardRegistry::load(board::BOARDS_DIR);
+        for e in errors {
+            println!("{} {}", "Ignoring board profile:".yellow(), e);
+        }
+        AppContext{
+            // libusb_context: libusb_context
+            devices: DeviceManager::new(),
+            // Scan & load projects/recipes
+            project_manager: manager::scan(),
+            current_project: None,
+            io: None,
+            armed: None,
+            cycle: 0,
+            last_capture: None,
+            wave: wave::WaveView::default(),
+            failed_checks: 0,
+            boards,
+            trigger: None,
+        }
+    }
 }
 
 
 /// The main CLI app loop
 pub fn run_cli() -> Result<()> {
     let mut prompt = Readline::default().prompt()?;
-    let threads: ThreadHandle = Arc::new(Mutex::new(HashMap::new()));
+    let _threads: ThreadHandle = Arc::new(Mutex::new(HashMap::new()));
 
     // Initialization tasks:
-    let mut app_context = AppContext{
-        // libusb_context: libusb_context
-        fde_devices: Vec::new(),
-        fde_handles: Arc::new(Mutex::new(HashMap::new())),
-        project_manager: ScanResult{ projects: Vec::new(), recipes: Vec::new() },
-        current_project: None,
-        io: None
-    };
-    // Scan & load projects/recipes
-    app_context.project_manager = manager::scan();
+    let mut app_context = AppContext::new();
 
     loop {
         // Show the shell prompt
         match prompt.run() {
             Ok(command) => {
                 // If the user enters a command, run it
-                if !command.trim().is_empty() {
-                    if !handle_command(&command, &mut app_context)? {
-                        // returning false -> exit
-                        break;
-                    }
+                if !command.trim().is_empty() && !handle_command(&command, &mut app_context)? {
+                    // returning false -> exit
+                    break;
                 }
             }
             Err(_) => {
@@ -717,6 +1945,30 @@ pub fn run_cli() -> Result<()> {
     Ok(())
 }
 
+/// Runs the commands of a script file (one per line, `#` comments) without the prompt.
+/// Returns whether every check of the script passed.
+pub fn run_script(path: &str) -> Result<bool> {
+    let script = std::fs::read_to_string(path)
+        .map_err(|e| anyhow::anyhow!("failed to read {}: {}", path, e))?;
+    let mut app_context = AppContext::new();
+
+    for command in script.lines().map(str::trim) {
+        if command.is_empty() || command.starts_with('#') {
+            continue;
+        }
+        println!("{} {}", ">".cyan(), command);
+        if !handle_command(command, &mut app_context)? {
+            break;
+        }
+    }
+
+    if app_context.failed_checks > 0 {
+        println!("{} {} checks failed or could not run", path.yellow(), app_context.failed_checks.to_string().red());
+    }
+    Ok(app_context.failed_checks == 0)
+}
+
+#[allow(dead_code)]
 fn print_bits(rx_buffer: Vec<u16>) {
     let mut current_bits = String::new();
     let mut bit_count = 0;
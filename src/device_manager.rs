@@ -0,0 +1,273 @@
+/*
+ * Filename: device_manager.rs
+ * Desciprtion: Owns the discovered FDE boards and the USB handles of the mounted ones,
+ * handles are closed when a board is unmounted (or the manager is dropped)
+ */
+
+use std::cell::Cell;
+use std::collections::HashMap;
+use std::fmt;
+
+use libusb1_sys as libusb_ffi;
+
+use crate::vlfd::{
+    helper::{get_usb_handle, libusb_get_context},
+    structs::{UsbDevice, UsbHandle}
+};
+
+/// Errors for selecting, mounting & unmounting boards.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DeviceError {
+    /// `discover` has not found any board (yet).
+    NoDevices,
+    /// The `<dev>` argument is not a number.
+    InvalidId(String),
+    /// The `<dev>` argument does not refer to a discovered board.
+    OutOfBounds { id: usize, count: usize },
+    NotMounted(usize),
+    AlreadyMounted(usize),
+    /// libusb could not open the board.
+    Usb(String),
+}
+
+impl fmt::Display for DeviceError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DeviceError::NoDevices => write!(f, "No fde_devices found, call `discover` first"),
+            DeviceError::InvalidId(id) => write!(f, "invalid device id \"{}\"", id),
+            DeviceError::OutOfBounds { id, count } => write!(f, "id {} is out of bounds ({} device(s) discovered)", id, count),
+            DeviceError::NotMounted(id) => write!(f, "Device {} is not mounted, call `mount {}` first", id, id),
+            DeviceError::AlreadyMounted(id) => write!(f, "Device {} is already mounted", id),
+            DeviceError::Usb(e) => write!(f, "failed to open USB device: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for DeviceError {}
+
+/// USB transfer behaviour of a session, changed with the `usb` command.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TransferSettings {
+    /// Time from the first attempt of a transfer after which it is no longer retried. It does not limit
+    /// a single transfer: an attempt that hangs in the driver blocks until the driver gives up on it
+    pub retry_deadline_ms: u64,
+    /// Retries of a failed transfer before the recovery sequence is run
+    pub retries: u32,
+    /// Delay before the first retry, doubled for every following one
+    pub backoff_ms: u64,
+    /// Reprogram the current project as part of the recovery sequence
+    pub reprogram: bool,
+}
+
+impl Default for TransferSettings {
+    fn default() -> Self {
+        Self { retry_deadline_ms: 1000, retries: 3, backoff_ms: 50, reprogram: false }
+    }
+}
+
+impl TransferSettings {
+    /// Changes a single setting by name (`deadline`, `retries`, `backoff` or `reprogram`).
+    pub fn set(&mut self, name: &str, value: &str) -> Result<(), String> {
+        let invalid = || format!("invalid value \"{}\" for {}", value, name);
+        match name {
+            "deadline" => {
+                let deadline: u64 = value.parse().map_err(|_| invalid())?;
+                if deadline == 0 {
+                    return Err("deadline must be at least 1 ms".to_string());
+                }
+                self.retry_deadline_ms = deadline;
+            }
+            "retries" => self.retries = value.parse().map_err(|_| invalid())?,
+            "backoff" => self.backoff_ms = value.parse().map_err(|_| invalid())?,
+            "reprogram" => {
+                self.reprogram = match value {
+                    "on" | "true" | "1" => true,
+                    "off" | "false" | "0" => false,
+                    _ => return Err(invalid()),
+                }
+            }
+            _ => return Err(format!("unknown setting \"{}\" (deadline, retries, backoff or reprogram)", name)),
+        }
+        Ok(())
+    }
+}
+
+/// A mounted board: owns its USB handle and keeps track of what has been done with it.
+pub struct BoardSession {
+    pub device: UsbDevice,
+    handle: UsbHandle,
+    io_open: Cell<bool>,
+    programmed: Cell<bool>,
+    settings: Cell<TransferSettings>,
+}
+
+impl BoardSession {
+    /// The handle to pass to `DeviceHandler`/`ProgramHandler`, it stays valid for the life of the session.
+    pub fn handle(&self) -> &UsbHandle {
+        &self.handle
+    }
+
+    pub fn is_io_open(&self) -> bool {
+        self.io_open.get()
+    }
+
+    pub fn set_io_open(&self, io_open: bool) {
+        self.io_open.set(io_open);
+    }
+
+    pub fn is_programmed(&self) -> bool {
+        self.programmed.get()
+    }
+
+    pub fn set_programmed(&self, programmed: bool) {
+        self.programmed.set(programmed);
+    }
+
+    pub fn settings(&self) -> TransferSettings {
+        self.settings.get()
+    }
+
+    pub fn set_settings(&self, settings: TransferSettings) {
+        self.settings.set(settings);
+    }
+}
+
+impl Drop for BoardSession {
+    fn drop(&mut self) {
+        if !self.handle.handle.is_null() {
+            // The session is the only owner of the handle, so it cannot be closed twice
+            unsafe { libusb_ffi::libusb_close(self.handle.handle) };
+        }
+    }
+}
+
+/// The discovered boards (indexed by their position in the last `discover`) and their sessions.
+#[derive(Default)]
+pub struct DeviceManager {
+    devices: Vec<UsbDevice>,
+    sessions: HashMap<UsbDevice, BoardSession>,
+}
+
+impl DeviceManager {
+    pub fn new() -> Self {
+        Self { devices: Vec::new(), sessions: HashMap::new() }
+    }
+
+    /// Replaces the discovered boards, sessions of boards that disappeared are closed.
+    pub fn set_devices(&mut self, devices: Vec<UsbDevice>) {
+        self.sessions.retain(|device, _| devices.contains(device));
+        self.devices = devices;
+    }
+
+    /// Parses the `<dev>` argument of a command and checks it against the discovered boards.
+    pub fn parse_id(&self, id: &str) -> Result<usize, DeviceError> {
+        if self.devices.is_empty() {
+            return Err(DeviceError::NoDevices);
+        }
+        let id: usize = id.parse().map_err(|_| DeviceError::InvalidId(id.to_string()))?;
+        if id >= self.devices.len() {
+            return Err(DeviceError::OutOfBounds { id, count: self.devices.len() });
+        }
+        Ok(id)
+    }
+
+    pub fn device(&self, id: usize) -> Result<&UsbDevice, DeviceError> {
+        if self.devices.is_empty() {
+            return Err(DeviceError::NoDevices);
+        }
+        self.devices.get(id).ok_or(DeviceError::OutOfBounds { id, count: self.devices.len() })
+    }
+
+    pub fn mount(&mut self, id: usize) -> Result<&BoardSession, DeviceError> {
+        let device = self.device(id)?.clone();
+        if self.sessions.contains_key(&device) {
+            return Err(DeviceError::AlreadyMounted(id));
+        }
+
+        let handle = get_usb_handle(device.bus, device.address, device.id_vendor, device.id_product)
+            .map_err(|e| DeviceError::Usb(e.to_string()))?;
+        let session = BoardSession {
+            device: device.clone(),
+            handle: UsbHandle { handle, context: libusb_get_context() },
+            io_open: Cell::new(false),
+            programmed: Cell::new(false),
+            settings: Cell::new(TransferSettings::default()),
+        };
+        Ok(self.sessions.entry(device).or_insert(session))
+    }
+
+    /// Unmounts a board, its handle is closed when the session is dropped.
+    pub fn unmount(&mut self, id: usize) -> Result<(), DeviceError> {
+        let device = self.device(id)?.clone();
+        self.sessions.remove(&device).map(|_| ()).ok_or(DeviceError::NotMounted(id))
+    }
+
+    pub fn session(&self, id: usize) -> Result<&BoardSession, DeviceError> {
+        let device = self.device(id)?;
+        self.sessions.get(device).ok_or(DeviceError::NotMounted(id))
+    }
+
+    /// Resolves the `<dev>` argument of a command to the session of a mounted board.
+    pub fn select(&self, id: &str) -> Result<(usize, &BoardSession), DeviceError> {
+        let id = self.parse_id(id)?;
+        Ok((id, self.session(id)?))
+    }
+
+    /// Mounted boards with their index, in discovery order.
+    pub fn sessions(&self) -> Vec<(usize, &BoardSession)> {
+        self.devices
+            .iter()
+            .enumerate()
+            .filter_map(|(id, device)| self.sessions.get(device).map(|session| (id, session)))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_no_devices() {
+        let manager = DeviceManager::new();
+        assert_eq!(manager.parse_id("0"), Err(DeviceError::NoDevices));
+        assert_eq!(manager.device(0).err(), Some(DeviceError::NoDevices));
+        assert_eq!(manager.session(0).err(), Some(DeviceError::NoDevices));
+        assert!(manager.select("0").is_err());
+        assert!(manager.sessions().is_empty());
+    }
+
+    #[test]
+    fn test_unmount_without_devices() {
+        let mut manager = DeviceManager::new();
+        assert_eq!(manager.unmount(0), Err(DeviceError::NoDevices));
+        assert!(manager.mount(3).is_err());
+    }
+
+    #[test]
+    fn test_error_messages() {
+        assert_eq!(DeviceError::InvalidId("x".to_string()).to_string(), "invalid device id \"x\"");
+        assert_eq!(
+            DeviceError::OutOfBounds { id: 2, count: 1 }.to_string(),
+            "id 2 is out of bounds (1 device(s) discovered)"
+        );
+        assert_eq!(DeviceError::NotMounted(1).to_string(), "Device 1 is not mounted, call `mount 1` first");
+    }
+
+    #[test]
+    fn test_transfer_settings() {
+        let mut settings = TransferSettings::default();
+        settings.set("deadline", "250").unwrap();
+        settings.set("retries", "5").unwrap();
+        settings.set("backoff", "10").unwrap();
+        settings.set("reprogram", "on").unwrap();
+        assert_eq!(settings, TransferSettings { retry_deadline_ms: 250, retries: 5, backoff_ms: 10, reprogram: true });
+
+        assert!(settings.set("deadline", "0").is_err());
+        assert!(settings.set("retries", "-1").is_err());
+        assert!(settings.set("reprogram", "maybe").is_err());
+        assert!(settings.set("speed", "1").is_err());
+        // Failed changes leave the settings untouched
+        assert_eq!(settings.retries, 5);
+    }
+}
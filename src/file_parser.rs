@@ -3,6 +3,7 @@ use std::io::{self, BufRead};
 use anyhow::Result;
 use std::io::Read;
 
+#[allow(dead_code)]
 fn read_hex_data_from_text_file<P: AsRef<std::path::Path>>(file_path: P) -> Result<Vec<String>> {
     let file = File::open(file_path)?;
     let reader = io::BufReader::new(file); // This is the BufReader
@@ -21,6 +22,7 @@ fn read_hex_data_from_text_file<P: AsRef<std::path::Path>>(file_path: P) -> Resu
     Ok(values)
 }
 
+#[allow(dead_code)]
 fn read_binary_data_from_file<P: AsRef<std::path::Path>>(file_path: P) -> Result<Vec<u8>> {
     let mut file = File::open(file_path)?;
     let mut buffer = Vec::new();
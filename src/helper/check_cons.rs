@@ -0,0 +1,195 @@
+/*
+ * Filename: check_cons.rs
+ * Desciprtion: Validation of the constraints (.xml) of a project against the pin map of the board,
+ * run before the IO of a project is used or its bitstream programmed
+ */
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+use crate::board::{BoardProfile, PeripheralKind};
+use crate::ports::{bus, ConstraintPort};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Error,
+    Warning,
+    Info,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Severity::Error => write!(f, "ERROR"),
+            Severity::Warning => write!(f, "WARNING"),
+            Severity::Info => write!(f, "INFO"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Finding {
+    pub severity: Severity,
+    pub message: String,
+}
+
+impl Finding {
+    fn new(severity: Severity, message: String) -> Self {
+        Self { severity, message }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Bank {
+    Input,
+    Output,
+}
+
+/// `clk`, `clock`, `clk_30M`, `sys_clk`...
+fn looks_like_clock(name: &str) -> bool {
+    let name = bus::parse_port_name(name).base.to_lowercase();
+    name.split(|c: char| !c.is_ascii_alphanumeric()).any(|word| word == "clk" || word == "clock")
+}
+
+fn bank(pin: &str, board: &BoardProfile) -> Option<Bank> {
+    if board.input.contains_key(pin) {
+        Some(Bank::Input)
+    } else if board.output.contains_key(pin) {
+        Some(Bank::Output)
+    } else {
+        None
+    }
+}
+
+/// Checks the constraints against the pin map & peripherals of the board profile: unknown pins, pins (or ports)
+/// assigned more than once, ports on clock pins or reset lines, clocks on VeriComm IO & buses with bits on both
+/// input and output pins.
+/// Findings are sorted by severity, errors first.
+pub fn check_cons(constraints: &[ConstraintPort], board: &BoardProfile) -> Vec<Finding> {
+    let mut findings = Vec::new();
+    let mut pins: BTreeMap<&str, Vec<&str>> = BTreeMap::new();
+    let mut names: BTreeMap<&str, usize> = BTreeMap::new();
+    // Bus name -> (bits on input pins, bits on output pins)
+    let mut buses: BTreeMap<String, (Vec<&str>, Vec<&str>)> = BTreeMap::new();
+
+    for constraint in constraints {
+        let (name, pin) = (constraint.name.as_str(), constraint.port_name.as_str());
+        pins.entry(pin).or_default().push(name);
+        *names.entry(name).or_default() += 1;
+
+        if let Some(clock) = board.clock(pin) {
+            findings.push(Finding::new(
+                Severity::Warning,
+                format!("{} is on {}, {} (not VeriComm IO, it cannot be driven or read)", name, pin, clock.describe()),
+            ));
+            continue;
+        }
+        if looks_like_clock(name) && bank(pin, board).is_some() {
+            findings.push(Finding::new(
+                Severity::Warning,
+                format!("{} looks like a clock but is on {}, VeriComm IO changes at most once per cycle", name, pin),
+            ));
+        }
+        if let Some((reset, _)) = board.peripheral(pin).filter(|(peripheral, _)| peripheral.kind == PeripheralKind::Reset) {
+            findings.push(Finding::new(Severity::Info, format!("{} is on {}, {}", name, pin, reset.describe())));
+        }
+        match bank(pin, board) {
+            Some(bank) => {
+                let port_name = bus::parse_port_name(name);
+                if port_name.index.is_some() {
+                    let (inputs, outputs) = buses.entry(port_name.base).or_default();
+                    match bank {
+                        Bank::Input => inputs.push(name),
+                        Bank::Output => outputs.push(name),
+                    }
+                }
+            }
+            None => findings.push(Finding::new(
+                Severity::Error,
+                format!("{} is on {}, which is not a pin of the board's VeriComm IO", name, pin),
+            )),
+        }
+    }
+
+    for (pin, ports) in pins.iter().filter(|(_, ports)| ports.len() > 1) {
+        findings.push(Finding::new(Severity::Error, format!("{} is assigned to more than one port: {}", pin, ports.join(", "))));
+    }
+    for (name, count) in names.iter().filter(|(_, count)| **count > 1) {
+        findings.push(Finding::new(Severity::Warning, format!("{} is constrained {} times", name, count)));
+    }
+    for (bus, (inputs, outputs)) in buses.iter().filter(|(_, (inputs, outputs))| !inputs.is_empty() && !outputs.is_empty()) {
+        findings.push(Finding::new(
+            Severity::Error,
+            format!("bus {} is split across input ({}) and output ({}) pins", bus, inputs.join(", "), outputs.join(", ")),
+        ));
+    }
+
+    findings.sort_by_key(|finding| finding.severity);
+    findings
+}
+
+/// Number of findings with the severity.
+pub fn count(findings: &[Finding], severity: Severity) -> usize {
+    findings.iter().filter(|finding| finding.severity == severity).count()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ports;
+
+    fn constraint(name: &str, pin: &str) -> ConstraintPort {
+        ConstraintPort { name: name.to_string(), port_name: pin.to_string() }
+    }
+
+    #[test]
+    fn test_check_afifo() {
+        let findings = check_cons(&ports::recipe_constraints("afifo_test"), &BoardProfile::fde());
+
+        // Only the two clocks, which cannot be driven or read
+        assert_eq!(count(&findings, Severity::Error), 0);
+        assert_eq!(count(&findings, Severity::Warning), 2);
+        assert_eq!(findings.len(), 2);
+        assert!(findings[0].message.starts_with("clk is on P77"));
+        assert!(findings[1].message.starts_with("clk_30M is on P185"));
+    }
+
+    #[test]
+    fn test_check_errors() {
+        let constraints = vec![
+            constraint("data[0]", "P151"),
+            constraint("data[1]", "P7"),
+            constraint("led", "P7"),
+            constraint("led", "P6"),
+            constraint("missing", "P999"),
+        ];
+        let findings = check_cons(&constraints, &BoardProfile::fde());
+
+        assert_eq!(count(&findings, Severity::Error), 3);
+        assert_eq!(count(&findings, Severity::Warning), 1);
+        let messages: Vec<&str> = findings.iter().map(|finding| finding.message.as_str()).collect();
+        assert!(messages.contains(&"missing is on P999, which is not a pin of the board's VeriComm IO"));
+        assert!(messages.contains(&"P7 is assigned to more than one port: data[1], led"));
+        assert!(messages.contains(&"bus data is split across input (data[0]) and output (data[1]) pins"));
+        assert!(messages.contains(&"led is constrained 2 times"));
+    }
+
+    #[test]
+    fn test_check_peripherals() {
+        // The FDE board with a made up reset line, its real reset wiring is not documented
+        let mut board = BoardProfile::fde();
+        board.peripherals.push(serde_json::from_str(r#"{"name": "rst_n", "kind": "reset", "pins": ["P151"], "active_low": true}"#).unwrap());
+        let constraints = vec![constraint("sys_clk", "P148"), constraint("rst", "P151"), constraint("clk_30M", "P185")];
+        let findings = check_cons(&constraints, &board);
+
+        let messages: Vec<&str> = findings.iter().map(|finding| finding.message.as_str()).collect();
+        assert_eq!(
+            messages,
+            vec![
+                "sys_clk looks like a clock but is on P148, VeriComm IO changes at most once per cycle",
+                "clk_30M is on P185, the on-board oscillator, clk_30M in afifo_test (not VeriComm IO, it cannot be driven or read)",
+                "rst is on P151, reset rst_n (active low)",
+            ]
+        );
+    }
+}
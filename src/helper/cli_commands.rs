@@ -37,25 +37,117 @@ pub fn show_help() {
             command: "fde_dump_conf",
             description: "", // Add a description if needed
         },
+        CommandHelp {
+            command: "clock {i}",
+            description: "Show the design clock high & low delays of FDE board {i}",
+        },
+        CommandHelp {
+            command: "conf save {i} {file}",
+            description: "Save the configuration of FDE board {i} to a JSON file",
+        },
+        CommandHelp {
+            command: "conf diff {a} {b}",
+            description: "Show the configuration fields that differ between two boards and/or files",
+        },
+        CommandHelp {
+            command: "flash info {i}",
+            description: "Show the flash geometry of FDE board {i} & the region the configuration space selects",
+        },
+        CommandHelp {
+            command: "selftest {i}",
+            description: "Check FDE board {i} end to end (cfg, reset, programming & IO) and print a report, programming & IO are skipped until recipes/loopback has a bitstream",
+        },
+        CommandHelp {
+            command: "usb {i} [setting value]",
+            description: "Show or set the retry deadline (ms, not a per-transfer timeout), retries, backoff (ms) & reprogram (on|off) on recovery for board {i}",
+        },
         CommandHelp {
             command: "lsd",
             description: "Lists connected devices",
         },
         CommandHelp {
-            command: "arm",
-            description: "Select and arm a libusb device",
+            command: "arm {i}",
+            description: "Select and arm FDE board {i}, used by commands without a device argument",
+        },
+        CommandHelp {
+            command: "step [n]",
+            description: "Send the current inputs to the armed board n times (default 1), printing the outputs after each: one cycle each if the engine gates the design clock, otherwise samples of the free-running design",
+        },
+        CommandHelp {
+            command: "set <port> <value>",
+            description: "Drive a value (hex 0x.., binary 0b.. or decimal) on an input port of the loaded project",
+        },
+        CommandHelp {
+            command: "format [<port> <format>]",
+            description: "Show or change how ports are displayed: dec, hex, bin, signed, ascii, level (HIGH/LOW), fixed:n or sfixed:n (n fractional bits)",
+        },
+        CommandHelp {
+            command: "apply [n]",
+            description: "Send the current inputs to the armed board for n cycles (default 1) and print the outputs",
+        },
+        CommandHelp {
+            command: "capture {i} <n> [--vcd file]",
+            description: "Record n cycles of FDE board {i} (inputs as driven, outputs as sampled), optionally as a VCD waveform",
+        },
+        CommandHelp {
+            command: "stimulus vcd <file> [--map sig=port] [--clock sig] [--vcd out]",
+            description: "Drive the inputs of the armed board from a VCD file (one cycle per timestamp or clock edge), capture the outputs & check them against the output signals of the file",
+        },
+        CommandHelp {
+            command: "run <file>",
+            description: "Run a testbench (set/tick/wait/repeat/expect on the project's port names) on the armed board and report each statement",
+        },
+        CommandHelp {
+            command: "wave [ports] [--from n] [--to m] [--zoom z]",
+            description: "Show the last capture as timing diagrams in the terminal",
+        },
+        CommandHelp {
+            command: "wave left|right [n] / wave zoom in|out|<z>",
+            description: "Scroll the waveform view by n cycles (half a screen by default) or change its zoom",
         },
         CommandHelp {
             command: "list",
             description: "List currently connected and detected libusb devices",
         },
+        CommandHelp {
+            command: "board [list|use {name}]",
+            description: "Show the active board profile (pin map, chip & clock pins), list the profiles or switch to one (the IO of a loaded project is mapped again)",
+        },
+        CommandHelp {
+            command: "decode uart|spi|i2c {signals...} [--csv file]",
+            description: "Decode UART (cycles/bit, --bits, --parity), SPI (sclk mosi, --miso, --cs, --mode, --bits, --lsb) or I2C (scl sda) from the last capture",
+        },
+        CommandHelp {
+            command: "lcd [--log]",
+            description: "Draw the HD44780 character LCD driven by the last capture (ports from \"lcd\" in meta.json, RS is lcd_rst by default, without RS the writes are only listed), --log lists the commands & writes",
+        },
+        CommandHelp {
+            command: "trigger [condition|clear]",
+            description: "Show or set the trigger on output ports, e.g. `o_rdata==0x3x && rise(o_wfull)` (==, !=, rise(), fall(), change(), &&, ||, !)",
+        },
+        CommandHelp {
+            command: "capture_trigger {i} [--pre n] [--post n] [--max n] [--vcd file]",
+            description: "Run FDE board {i} until the trigger fires and keep the cycles around it as the last capture",
+        },
+        CommandHelp {
+            command: "monitor {i} [ports...]",
+            description: "Poll the outputs of FDE board {i} live with the current inputs, `set <port> <value>` changes an input meanwhile, `q` stops",
+        },
+        CommandHelp {
+            command: "check_cons",
+            description: "Check the constraints of the loaded project against the board (unknown/duplicate pins, clock pins, split buses)",
+        },
+        CommandHelp {
+            command: "program {i} [--force]",
+            description: "Program the bitstream of the loaded project to FDE board {i}, refused on constraint errors unless forced",
+        },
         CommandHelp {
             command: "reconfigure",
             description: "Go through the constraint and bitstream selection again",
         },
         CommandHelp {
-            command: "test",
-            description: "Options for testing",
+            command: "test {i}",
+            description: "Send a fixed test stimulus to FDE board {i} and print the outputs (nothing is checked, use `run` or `stimulus` for checks)",
         },
     ];
 
@@ -1,4 +1,4 @@
-/**
+/*
  * Filename: constraints.rs
  * Desciprtion: Handles the reading and parsing of the constriant .xml file
  * Checking if the required ports are matched, and matched correctly
@@ -32,8 +32,8 @@ impl ConstraintsReader {
          // Iterate through the XML events
          for e in parser {
              match e {
-                 Ok(XmlEvent::StartElement { name, attributes, .. }) => {
-                     if name.local_name == "port" {
+                 Ok(XmlEvent::StartElement { name, attributes, .. })
+                     if name.local_name == "port" => {
                          let mut port_name = String::new();
                          let mut position = String::new();
  
@@ -50,7 +50,6 @@ impl ConstraintsReader {
                          // Store the port information in the vector
                          self.ports.push(ConstraintPort{ name: port_name, port_name: position});
                      }
-                 }
                  // Ok(XmlEvent::EndElement { name }) => {
                  //     // Handle closing tags (not needed in this case)
                  // }
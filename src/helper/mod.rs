@@ -1,4 +1,5 @@
 pub mod smims_cfg;
 pub mod cli_commands;
 pub mod constraints;
-pub mod bitstream;
\ No newline at end of file
+pub mod bitstream;
+pub mod check_cons;
\ No newline at end of file
@@ -1,12 +1,188 @@
-/**
+/*
  * Filename: smims_cfg.rs
- * Desciprtion: A helper class that outputs a "printable" table for the Tabled library
+ * Desciprtion: A helper class that outputs a "printable" table for the Tabled library,
+ * and a field-level view of the SMIMS configuration space
  */
 
+use std::collections::BTreeMap;
+use std::fs;
+use serde::{Serialize, Deserialize};
 use tabled::Tabled;
 
 use crate::vlfd::cfg::CfgInfo;
 
+/// Every field of the SMIMS configuration space that `CfgInfo` exposes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CfgField {
+    ClockHighDelay,
+    ClockLowDelay,
+    Isv,
+    ClockcheckEnable,
+    SdkChannelSelector,
+    ModeSelector,
+    FlashBeginBlockAddr,
+    FlashBeginClusterAddr,
+    FlashReadEndBlockAddr,
+    FlashReadEndClusterAddr,
+    SecurityKey,
+    SmimsVersion,
+    SmimsMajorVersion,
+    SmimsSubVersion,
+    SmimsSubsubVersion,
+    FifoSize,
+    FlashTotalBlock,
+    FlashBlockSize,
+    FlashClusterSize,
+    VericommAbility,
+    VeriInstrumentAbility,
+    VeriLinkAbility,
+    VeriSocAbility,
+    VericommProAbility,
+    VeriSdkAbility,
+    IsProgrammed,
+    IsPcbConnect,
+    IsVericommClockcontinue,
+}
+
+impl CfgField {
+    /// All fields, in the order they are displayed.
+    pub const ALL: [CfgField; 28] = [
+        CfgField::ClockHighDelay,
+        CfgField::ClockLowDelay,
+        CfgField::Isv,
+        CfgField::ClockcheckEnable,
+        CfgField::SdkChannelSelector,
+        CfgField::ModeSelector,
+        CfgField::FlashBeginBlockAddr,
+        CfgField::FlashBeginClusterAddr,
+        CfgField::FlashReadEndBlockAddr,
+        CfgField::FlashReadEndClusterAddr,
+        CfgField::SecurityKey,
+        CfgField::SmimsVersion,
+        CfgField::SmimsMajorVersion,
+        CfgField::SmimsSubVersion,
+        CfgField::SmimsSubsubVersion,
+        CfgField::FifoSize,
+        CfgField::FlashTotalBlock,
+        CfgField::FlashBlockSize,
+        CfgField::FlashClusterSize,
+        CfgField::VericommAbility,
+        CfgField::VeriInstrumentAbility,
+        CfgField::VeriLinkAbility,
+        CfgField::VeriSocAbility,
+        CfgField::VericommProAbility,
+        CfgField::VeriSdkAbility,
+        CfgField::IsProgrammed,
+        CfgField::IsPcbConnect,
+        CfgField::IsVericommClockcontinue,
+    ];
+
+    /// The name of the field in configuration snapshots (e.g. `"clock_high_delay"`).
+    pub fn key(&self) -> &'static str {
+        match self {
+            CfgField::ClockHighDelay => "clock_high_delay",
+            CfgField::ClockLowDelay => "clock_low_delay",
+            CfgField::Isv => "isv",
+            CfgField::ClockcheckEnable => "clockcheck_enable",
+            CfgField::SdkChannelSelector => "sdk_channel_selector",
+            CfgField::ModeSelector => "mode_selector",
+            CfgField::FlashBeginBlockAddr => "flash_begin_block_addr",
+            CfgField::FlashBeginClusterAddr => "flash_begin_cluster_addr",
+            CfgField::FlashReadEndBlockAddr => "flash_read_end_block_addr",
+            CfgField::FlashReadEndClusterAddr => "flash_read_end_cluster_addr",
+            CfgField::SecurityKey => "security_key",
+            CfgField::SmimsVersion => "smims_version",
+            CfgField::SmimsMajorVersion => "smims_major_version",
+            CfgField::SmimsSubVersion => "smims_sub_version",
+            CfgField::SmimsSubsubVersion => "smims_subsub_version",
+            CfgField::FifoSize => "fifo_size",
+            CfgField::FlashTotalBlock => "flash_total_block",
+            CfgField::FlashBlockSize => "flash_block_size",
+            CfgField::FlashClusterSize => "flash_cluster_size",
+            CfgField::VericommAbility => "vericomm_ability",
+            CfgField::VeriInstrumentAbility => "veri_instrument_ability",
+            CfgField::VeriLinkAbility => "veri_link_ability",
+            CfgField::VeriSocAbility => "veri_soc_ability",
+            CfgField::VericommProAbility => "vericomm_pro_ability",
+            CfgField::VeriSdkAbility => "veri_sdk_ability",
+            CfgField::IsProgrammed => "is_programmed",
+            CfgField::IsPcbConnect => "is_pcb_connect",
+            CfgField::IsVericommClockcontinue => "is_vericomm_clockcontinue",
+        }
+    }
+
+    /// The human readable name shown in the configuration table.
+    pub fn label(&self) -> &'static str {
+        match self {
+            CfgField::ClockHighDelay => "Clock High Delay",
+            CfgField::ClockLowDelay => "Clock Low Delay",
+            CfgField::Isv => "Vericomm ISV",
+            CfgField::ClockcheckEnable => "Vericomm Clockcheck Enable",
+            CfgField::SdkChannelSelector => "Veri SDK Channel Selector",
+            CfgField::ModeSelector => "Mode Selector",
+            CfgField::FlashBeginBlockAddr => "Flash Begin Block Addr",
+            CfgField::FlashBeginClusterAddr => "Flash Begin Cluster Addr",
+            CfgField::FlashReadEndBlockAddr => "Flash Read End Block Addr",
+            CfgField::FlashReadEndClusterAddr => "Flash Read End Cluster Addr",
+            CfgField::SecurityKey => "Security Key",
+            CfgField::SmimsVersion => "SMIMS Version",
+            CfgField::SmimsMajorVersion => "SMIMS Major Version",
+            CfgField::SmimsSubVersion => "SMIMS Sub Version",
+            CfgField::SmimsSubsubVersion => "SMIMS Subsub Version",
+            CfgField::FifoSize => "FIFO Size",
+            CfgField::FlashTotalBlock => "Flash Total Block",
+            CfgField::FlashBlockSize => "Flash Block Size",
+            CfgField::FlashClusterSize => "Flash Cluster Size",
+            CfgField::VericommAbility => "Vericomm Ability",
+            CfgField::VeriInstrumentAbility => "Veri Instrument Ability",
+            CfgField::VeriLinkAbility => "Veri Link Ability",
+            CfgField::VeriSocAbility => "Veri SOC Ability",
+            CfgField::VericommProAbility => "Vericomm Pro Ability",
+            CfgField::VeriSdkAbility => "Veri SDK Ability",
+            CfgField::IsProgrammed => "Is Programmed",
+            CfgField::IsPcbConnect => "Is PCB Connect",
+            CfgField::IsVericommClockcontinue => "Is Vericomm Clockcontinue",
+        }
+    }
+
+    /// Reads the field from a configuration, booleans are reported as 0/1.
+    // The casts keep the fields u16 whatever width a vlfd version gives its getters
+    #[allow(clippy::unnecessary_cast)]
+    pub fn read(&self, cfg: &impl CfgInfo) -> u16 {
+        match self {
+            CfgField::ClockHighDelay => cfg.get_vericomm_clock_highdelay() as u16,
+            CfgField::ClockLowDelay => cfg.get_vericomm_clock_lowdelay() as u16,
+            CfgField::Isv => cfg.get_vericomm_isv() as u16,
+            CfgField::ClockcheckEnable => cfg.get_vericomm_clockcheck_enable() as u16,
+            CfgField::SdkChannelSelector => cfg.get_veri_sdk_channel_selector() as u16,
+            CfgField::ModeSelector => cfg.get_mode_selector() as u16,
+            CfgField::FlashBeginBlockAddr => cfg.get_flash_begin_block_addr() as u16,
+            CfgField::FlashBeginClusterAddr => cfg.get_flash_begin_cluster_addr() as u16,
+            CfgField::FlashReadEndBlockAddr => cfg.get_flash_read_end_block_addr() as u16,
+            CfgField::FlashReadEndClusterAddr => cfg.get_flash_read_end_cluster_addr() as u16,
+            CfgField::SecurityKey => cfg.get_security_key() as u16,
+            CfgField::SmimsVersion => cfg.smims_version() as u16,
+            CfgField::SmimsMajorVersion => cfg.smims_majorversion() as u16,
+            CfgField::SmimsSubVersion => cfg.smims_subversion() as u16,
+            CfgField::SmimsSubsubVersion => cfg.smims_subsubversion() as u16,
+            CfgField::FifoSize => cfg.fifo_size() as u16,
+            CfgField::FlashTotalBlock => cfg.flash_total_block() as u16,
+            CfgField::FlashBlockSize => cfg.flash_block_size() as u16,
+            CfgField::FlashClusterSize => cfg.flash_cluster_size() as u16,
+            CfgField::VericommAbility => cfg.vericomm_ability() as u16,
+            CfgField::VeriInstrumentAbility => cfg.veri_instrument_ability() as u16,
+            CfgField::VeriLinkAbility => cfg.veri_link_ability() as u16,
+            CfgField::VeriSocAbility => cfg.veri_soc_ability() as u16,
+            CfgField::VericommProAbility => cfg.vericomm_pro_ability() as u16,
+            CfgField::VeriSdkAbility => cfg.veri_sdk_ability() as u16,
+            CfgField::IsProgrammed => cfg.is_programmed() as u16,
+            CfgField::IsPcbConnect => cfg.is_pcb_connect() as u16,
+            CfgField::IsVericommClockcontinue => cfg.is_vericomm_clockcontinue() as u16,
+        }
+    }
+
+}
+
 // A single row in a vertical table that maps a configuration field to its value.
 #[derive(Tabled)]
 pub struct CfgTable {
@@ -19,37 +195,111 @@ pub struct CfgTable {
 impl CfgTable {
     /// Creates a vertical representation (as a vector of rows) of any configuration that implements `CfgInfo`.
     ///
-    /// Extend this list with all fields you wish to display.
+    /// Extend `CfgField` with all fields you wish to display.
     pub fn from_cfg(cfg: &impl CfgInfo) -> Vec<Self> {
-        vec![
-            Self { field: "Clock High Delay", value: format!("{:#04x}", cfg.get_vericomm_clock_highdelay()) },
-            Self { field: "Clock Low Delay", value: format!("{:#04x}", cfg.get_vericomm_clock_lowdelay()) },
-            Self { field: "Vericomm ISV", value: format!("{:#04x}", cfg.get_vericomm_isv()) },
-            Self { field: "Vericomm Clockcheck Enable", value: format!("{:#04x}", if cfg.get_vericomm_clockcheck_enable() { 1 } else { 0 }) },
-            Self { field: "Veri SDK Channel Selector", value: format!("{:#04x}", cfg.get_veri_sdk_channel_selector()) },
-            Self { field: "Mode Selector", value: format!("{:#04x}", cfg.get_mode_selector()) },
-            Self { field: "Flash Begin Block Addr", value: format!("{:#04x}", cfg.get_flash_begin_block_addr()) },
-            Self { field: "Flash Begin Cluster Addr", value: format!("{:#04x}", cfg.get_flash_begin_cluster_addr()) },
-            Self { field: "Flash Read End Block Addr", value: format!("{:#04x}", cfg.get_flash_read_end_block_addr()) },
-            Self { field: "Flash Read End Cluster Addr", value: format!("{:#04x}", cfg.get_flash_read_end_cluster_addr()) },
-            Self { field: "Security Key", value: format!("{:#04x}", cfg.get_security_key()) },
-            Self { field: "SMIMS Version", value: format!("{:#04x}", cfg.smims_version()) },
-            Self { field: "SMIMS Major Version", value: format!("{:#04x}", cfg.smims_majorversion()) },
-            Self { field: "SMIMS Sub Version", value: format!("{:#04x}", cfg.smims_subversion()) },
-            Self { field: "SMIMS Subsub Version", value: format!("{:#04x}", cfg.smims_subsubversion()) },
-            Self { field: "FIFO Size", value: format!("{:#04x}", cfg.fifo_size()) },
-            Self { field: "Flash Total Block", value: format!("{:#04x}", cfg.flash_total_block()) },
-            Self { field: "Flash Block Size", value: format!("{:#04x}", cfg.flash_block_size()) },
-            Self { field: "Flash Cluster Size", value: format!("{:#04x}", cfg.flash_cluster_size()) },
-            Self { field: "Vericomm Ability", value: format!("{:#04x}", if cfg.vericomm_ability() { 1 } else { 0 }) },
-            Self { field: "Veri Instrument Ability", value: format!("{:#04x}", if cfg.veri_instrument_ability() { 1 } else { 0 }) },
-            Self { field: "Veri Link Ability", value: format!("{:#04x}", if cfg.veri_link_ability() { 1 } else { 0 }) },
-            Self { field: "Veri SOC Ability", value: format!("{:#04x}", if cfg.veri_soc_ability() { 1 } else { 0 }) },
-            Self { field: "Vericomm Pro Ability", value: format!("{:#04x}", if cfg.vericomm_pro_ability() { 1 } else { 0 }) },
-            Self { field: "Veri SDK Ability", value: format!("{:#04x}", if cfg.veri_sdk_ability() { 1 } else { 0 }) },
-            Self { field: "Is Programmed", value: format!("{:#04x}", if cfg.is_programmed() { 1 } else { 0 }) },
-            Self { field: "Is PCB Connect", value: format!("{:#04x}", if cfg.is_pcb_connect() { 1 } else { 0 }) },
-            Self { field: "Is Vericomm Clockcontinue", value: format!("{:#04x}", if cfg.is_vericomm_clockcontinue() { 1 } else { 0 }) },
-        ]
-    }
-}
\ No newline at end of file
+        CfgField::ALL
+            .iter()
+            .map(|field| Self { field: field.label(), value: format!("{:#04x}", field.read(cfg)) })
+            .collect()
+    }
+}
+
+/// Every field of a configuration space, serializable to/from JSON so that boards can be compared.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CfgSnapshot {
+    /// Where the snapshot was taken from (a device description or a file name).
+    pub source: String,
+    /// Field key (see `CfgField::key`) -> value
+    pub fields: BTreeMap<String, u16>,
+}
+
+impl CfgSnapshot {
+    pub fn from_cfg(source: &str, cfg: &impl CfgInfo) -> Self {
+        let fields = CfgField::ALL
+            .iter()
+            .map(|field| (field.key().to_string(), field.read(cfg)))
+            .collect();
+        Self { source: source.to_string(), fields }
+    }
+
+    pub fn load(path: &str) -> Result<Self, String> {
+        let json = fs::read_to_string(path)
+            .map_err(|e| format!("failed to read snapshot {}: {}", path, e))?;
+        serde_json::from_str(&json)
+            .map_err(|e| format!("failed to parse snapshot {}: {}", path, e))
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        let json = serde_json::to_string_pretty(self)
+            .map_err(|e| format!("failed to serialize snapshot: {}", e))?;
+        fs::write(path, json)
+            .map_err(|e| format!("failed to write snapshot {}: {}", path, e))
+    }
+
+    pub fn get(&self, field: CfgField) -> Option<u16> {
+        self.fields.get(field.key()).copied()
+    }
+}
+
+/// A single row of a configuration diff, only differing fields are turned into rows.
+#[derive(Tabled)]
+pub struct CfgDiffTable {
+    field: &'static str,
+    a: String,
+    b: String,
+}
+
+impl CfgDiffTable {
+    /// Compares two snapshots field by field, fields missing from a snapshot are shown as "-".
+    pub fn from_snapshots(a: &CfgSnapshot, b: &CfgSnapshot) -> Vec<Self> {
+        let show = |value: Option<u16>| value.map_or("-".to_string(), |v| format!("{:#04x}", v));
+
+        CfgField::ALL
+            .iter()
+            .filter(|field| a.get(**field) != b.get(**field))
+            .map(|field| Self { field: field.label(), a: show(a.get(*field)), b: show(b.get(*field)) })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_field_keys() {
+        // Snapshots are keyed by field, so every key has to be unique
+        let keys: std::collections::BTreeSet<&str> = CfgField::ALL.iter().map(|field| field.key()).collect();
+        assert_eq!(keys.len(), CfgField::ALL.len());
+    }
+
+    #[test]
+    fn test_snapshot_diff() {
+        let mut fields: BTreeMap<String, u16> = CfgField::ALL.iter().map(|f| (f.key().to_string(), 0)).collect();
+        let a = CfgSnapshot { source: "a".to_string(), fields: fields.clone() };
+
+        fields.insert("clock_high_delay".to_string(), 0x0b);
+        fields.insert("smims_version".to_string(), 0x02);
+        fields.remove("fifo_size");
+        let b = CfgSnapshot { source: "b".to_string(), fields };
+
+        let diff = CfgDiffTable::from_snapshots(&a, &b);
+        let labels: Vec<&str> = diff.iter().map(|row| row.field).collect();
+        assert_eq!(labels, vec!["Clock High Delay", "SMIMS Version", "FIFO Size"]);
+        assert_eq!(diff[0].b, "0x0b");
+        assert_eq!(diff[2].b, "-");
+
+        assert!(CfgDiffTable::from_snapshots(&a, &a).is_empty());
+    }
+
+    #[test]
+    fn test_snapshot_save_load() {
+        let fields = CfgField::ALL.iter().map(|f| (f.key().to_string(), 0x1)).collect();
+        let snapshot = CfgSnapshot { source: "device 0".to_string(), fields };
+
+        let file = tempfile::NamedTempFile::new().unwrap();
+        let path = file.path().to_str().unwrap();
+        snapshot.save(path).unwrap();
+        assert_eq!(CfgSnapshot::load(path).unwrap(), snapshot);
+    }
+}
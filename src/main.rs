@@ -5,6 +5,8 @@ mod ports;              // Encoding & decoding SMIMS VLFD IO port data
 mod helper;             // Helper functions
 mod utilities;          // Major features will be implemented here
 mod manager;            // Project/recipe manager
+mod device_manager;     // Discovered FDE boards & their USB sessions
+mod board;              // Board profiles (pin map, chip, clock pins & frame layout)
 mod file_parser;        // various ways of reading data from a file & parsing it into a stream of bits
 
 use anyhow::Result;
@@ -14,7 +16,15 @@ fn main() -> Result<()> {
     println!("{}", constants::GREETING);
     println!("Brought to you by {}", constants::AUTHOR.green());
     
-    cli::run_cli()?;
+    // `fde_cli <script>` runs the commands of a script, failing (exit status 1) when a check fails or cannot run
+    match std::env::args().nth(1) {
+        Some(script) => {
+            if !cli::run_script(&script)? {
+                std::process::exit(1);
+            }
+        }
+        None => cli::run_cli()?,
+    }
     
     Ok(())
 }
\ No newline at end of file
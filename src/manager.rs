@@ -1,8 +1,11 @@
+use std::collections::BTreeMap;
 use std::fs;
 use std::path::{Path, PathBuf};
 use regex::Regex;
 use serde::{Serialize, Deserialize};
 
+use crate::utilities::lcd::LcdMapping;
+
 /// Represents a subfolder entry that has the three required files.
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct FileEntry {
@@ -18,6 +21,33 @@ pub struct FileEntry {
     pub meta: PathBuf,
 }
 
+/// The meta file (`<folder>_meta.json`) of a project.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct ProjectMeta {
+    #[serde(default)]
+    pub project_name: String,
+    #[serde(default)]
+    pub description: String,
+    /// Display format of the IO ports (port name -> `hex`, `bin`, `signed`, `ascii`, `level`, `fixed:<n>`...)
+    #[serde(default)]
+    pub formats: BTreeMap<String, String>,
+    /// Declared range of buses (bus name -> `[msb:lsb]`), e.g. `[0:7]` for a bus whose bit 0 is the MSB
+    #[serde(default)]
+    pub buses: BTreeMap<String, String>,
+    /// Board profile the project is for (see `board list`), the active one if not set
+    #[serde(default)]
+    pub board: Option<String>,
+    /// Ports driving an HD44780 character LCD (see `lcd`)
+    #[serde(default)]
+    pub lcd: Option<LcdMapping>,
+}
+
+/// Reads the meta file of a project.
+pub fn read_meta(entry: &FileEntry) -> Result<ProjectMeta, String> {
+    let text = fs::read_to_string(&entry.meta).map_err(|e| format!("failed to read {}: {}", entry.meta.display(), e))?;
+    serde_json::from_str(&text).map_err(|e| format!("invalid {}: {}", entry.meta.display(), e))
+}
+
 /// Top-level scan result containing separate lists for projects and recipes.
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ScanResult {
@@ -40,17 +70,16 @@ fn scan_dir(base: &str) -> Vec<FileEntry> {
     let mut entries = Vec::new();
     let base_path = Path::new(base);
 
-    if base_path.exists() && base_path.is_dir() {
-        if let Ok(dir_entries) = fs::read_dir(base_path) {
-            for entry in dir_entries.flatten() {
-                let path = entry.path();
-                if path.is_dir() {
-                    if let Some(folder_name) = path.file_name().and_then(|s| s.to_str()) {
-                        if let Some(file_entry) = scan_folder(&path, folder_name) {
-                            entries.push(file_entry);
-                        }
-                    }
-                }
+    if base_path.is_dir()
+        && let Ok(dir_entries) = fs::read_dir(base_path)
+    {
+        for entry in dir_entries.flatten() {
+            let path = entry.path();
+            if path.is_dir()
+                && let Some(folder_name) = path.file_name().and_then(|s| s.to_str())
+                && let Some(file_entry) = scan_folder(&path, folder_name)
+            {
+                entries.push(file_entry);
             }
         }
     }
@@ -113,4 +142,13 @@ mod tests {
         let result = scan();
         println!("{:#?}", result);
     }
+
+    #[test]
+    fn test_read_meta() {
+        let result = scan();
+        let entry = find_file_entry_by_folder(&result.recipes, "afifo_test").unwrap();
+        let meta = read_meta(entry).unwrap();
+        assert_eq!(meta.project_name, "Asynchronous FIFO demo V1");
+        assert_eq!(meta.formats.get("o_rdata").map(String::as_str), Some("hex"));
+    }
 }
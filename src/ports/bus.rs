@@ -0,0 +1,263 @@
+/*
+ * Filename: bus.rs
+ * Desciprtion: HDL port names of the constraints (escaped identifiers, multi-dimensional arrays,
+ * struct-flattened names & part selects) split into the bus they belong to & their bit index,
+ * and the declared ranges (`[7:0]`, `[0:7]`) that decide which bit of the bus value an index is
+ */
+
+use std::collections::BTreeMap;
+
+use super::IOPort;
+
+/// A constraint port name split into the name of the bus and the declared bit index (the last dimension).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PortName {
+    /// `mem[1]` for `mem[1][3]`, `bus.data` for `bus.data[2]`, `\a+b` for the escaped `\a+b [0]`
+    pub base: String,
+    pub index: Option<usize>,
+    /// Set for a part select of several bits like `addr[15:8]`, which cannot be the single pin of a constraint
+    pub part_select: Option<Range>,
+}
+
+/// Parses the select at the end of a name, `[3]` or a part select like `[3:3]` or `[15:8]`.
+/// Returns the start of the select & the selected range.
+fn trailing_select(name: &str) -> Option<(usize, Range)> {
+    let inner = name.strip_suffix(']')?;
+    let open = inner.rfind('[')?;
+    let select = &inner[open + 1..];
+    let range = match select.split_once(':') {
+        Some((msb, lsb)) => Range { msb: msb.trim().parse().ok()?, lsb: lsb.trim().parse().ok()? },
+        None => {
+            let index = select.trim().parse().ok()?;
+            Range { msb: index, lsb: index }
+        }
+    };
+    Some((open, range))
+}
+
+/// Splits a constraint port name into its bus & bit index, e.g. `addr[15]` -> (`addr`, 15).
+/// An escaped identifier (`\name`) ends at the first whitespace, only a select after it is a bit index.
+/// A part select of several bits is kept in the name & reported as `part_select`.
+pub fn parse_port_name(name: &str) -> PortName {
+    let name = name.trim();
+    let (identifier, rest) = match name.strip_prefix('\\') {
+        Some(escaped) => {
+            let end = escaped.find(char::is_whitespace).map_or(name.len(), |end| end + 1);
+            (&name[..end], name[end..].trim())
+        }
+        None => ("", name),
+    };
+
+    match trailing_select(rest) {
+        Some((open, range)) if range.width() == 1 => {
+            let base = format!("{}{}", identifier, rest[..open].trim_end());
+            PortName { base, index: Some(range.lsb), part_select: None }
+        }
+        select => PortName {
+            base: format!("{}{}", identifier, rest),
+            index: None,
+            part_select: select.map(|(_, range)| range),
+        },
+    }
+}
+
+/// The declared range of a bus, `[msb:lsb]` as in HDL: `[7:0]` makes index 0 the least significant bit,
+/// `[0:7]` makes index 0 the most significant one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Range {
+    pub msb: usize,
+    pub lsb: usize,
+}
+
+impl Range {
+    /// Parses `[msb:lsb]` (the brackets are optional).
+    pub fn parse(range: &str) -> Result<Self, String> {
+        let inner = range.trim();
+        let inner = inner.strip_prefix('[').and_then(|inner| inner.strip_suffix(']')).unwrap_or(inner);
+        let parsed = inner
+            .split_once(':')
+            .and_then(|(msb, lsb)| Some((msb.trim().parse().ok()?, lsb.trim().parse().ok()?)));
+        match parsed {
+            Some((msb, lsb)) => Ok(Range { msb, lsb }),
+            None => Err(format!("invalid range \"{}\", expected [msb:lsb]", range)),
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.msb.abs_diff(self.lsb) + 1
+    }
+
+    /// Position of a declared index in the bus value (0 is the least significant bit), `None` if out of the range.
+    pub fn position(&self, index: usize) -> Option<usize> {
+        let (low, high) = (self.msb.min(self.lsb), self.msb.max(self.lsb));
+        (low..=high).contains(&index).then(|| index.abs_diff(self.lsb))
+    }
+
+    pub fn contains(&self, index: usize) -> bool {
+        self.position(index).is_some()
+    }
+}
+
+impl std::fmt::Display for Range {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "[{}:{}]", self.msb, self.lsb)
+    }
+}
+
+/// Applies the declared ranges of a project (bus name -> `[msb:lsb]`) to its IO ports, returning what could not be applied.
+pub fn apply_declarations(io: &mut [IOPort], declarations: &BTreeMap<String, String>) -> Vec<String> {
+    let mut errors = Vec::new();
+    for (name, range) in declarations {
+        let Some(port) = super::find_port_mut(io, name) else {
+            errors.push(format!("\"{}\" is not a port of the project", name));
+            continue;
+        };
+        if let Err(e) = Range::parse(range).and_then(|range| port.declare(range)) {
+            errors.push(e);
+        }
+    }
+    errors
+}
+
+/// Formats a list of bit indices compactly, e.g. `3, 5-7`.
+fn format_indices(indices: &[usize]) -> String {
+    let mut ranges: Vec<String> = Vec::new();
+    let mut start = 0;
+    while start < indices.len() {
+        let mut end = start;
+        while end + 1 < indices.len() && indices[end + 1] == indices[end] + 1 {
+            end += 1;
+        }
+        ranges.push(if end == start {
+            indices[start].to_string()
+        } else {
+            format!("{}-{}", indices[start], indices[end])
+        });
+        start = end + 1;
+    }
+    ranges.join(", ")
+}
+
+/// Problems with the declared bits of the buses: part selects of several bits, bits constrained more than once,
+/// gaps between the lowest & the highest bit and buses mixing indexed & plain names.
+pub fn bus_warnings(io: &[IOPort]) -> Vec<String> {
+    let mut warnings = Vec::new();
+    for port in io {
+        for constraint in &port.ports {
+            if let Some(range) = parse_port_name(&constraint.name).part_select {
+                warnings.push(format!(
+                    "{} selects {} bits but is constrained to the single pin {}, constrain each bit on its own",
+                    constraint.name,
+                    range.width(),
+                    constraint.pin_name
+                ));
+            }
+        }
+
+        let indices: Vec<Option<usize>> = port.ports.iter().map(|port| port.index()).collect();
+        if indices.iter().all(Option::is_none) {
+            continue;
+        }
+        if indices.iter().any(Option::is_none) {
+            warnings.push(format!("{} is constrained both as a bus and as a single port", port.io_name));
+        }
+
+        let mut counts: BTreeMap<usize, usize> = BTreeMap::new();
+        for index in indices.iter().flatten() {
+            *counts.entry(*index).or_default() += 1;
+        }
+        let duplicates: Vec<usize> = counts.iter().filter(|(_, count)| **count > 1).map(|(index, _)| *index).collect();
+        if !duplicates.is_empty() {
+            warnings.push(format!("{}: bit(s) {} are constrained more than once", port.io_name, format_indices(&duplicates)));
+        }
+
+        let range = port.range();
+        let (low, high) = (range.msb.min(range.lsb), range.msb.max(range.lsb));
+        let missing: Vec<usize> = (low..=high).filter(|index| !counts.contains_key(index)).collect();
+        if !missing.is_empty() {
+            warnings.push(format!("{}{} has no pins for bit(s) {}", port.io_name, range, format_indices(&missing)));
+        }
+    }
+    warnings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ports::{self, ConstraintPort};
+
+    fn name(base: &str, index: Option<usize>) -> PortName {
+        PortName { base: base.to_string(), index, part_select: None }
+    }
+
+    #[test]
+    fn test_parse_port_name() {
+        assert_eq!(parse_port_name("clk"), name("clk", None));
+        assert_eq!(parse_port_name("data[15]"), name("data", Some(15)));
+        assert_eq!(parse_port_name("data [ 3 ]"), name("data", Some(3)));
+        assert_eq!(parse_port_name("mem[1][3]"), name("mem[1]", Some(3)));
+        assert_eq!(parse_port_name("bus.data[2]"), name("bus.data", Some(2)));
+        assert_eq!(parse_port_name("addr[9:9]"), name("addr", Some(9)));
+        // A part select of several bits cannot be a single pin, it is kept as the name & reported
+        assert_eq!(
+            parse_port_name("addr[15:8]"),
+            PortName { base: "addr[15:8]".to_string(), index: None, part_select: Some(Range { msb: 15, lsb: 8 }) }
+        );
+        // Escaped identifiers keep their brackets, only a select after the whitespace is an index
+        assert_eq!(parse_port_name("\\bus[3]"), name("\\bus[3]", None));
+        assert_eq!(parse_port_name("\\a+b [7]"), name("\\a+b", Some(7)));
+        assert_eq!(parse_port_name("\\cpu.reg[0] [1]"), name("\\cpu.reg[0]", Some(1)));
+    }
+
+    #[test]
+    fn test_group_by_declared_index() {
+        let port_mappings = ports::fde_parse_ports().unwrap();
+        let constraints = [
+            ("addr[10]", "P7"),
+            ("addr[8]", "P6"),
+            ("addr[12]", "P5"),
+            ("addr[8]", "P4"),
+            ("mem[1][0]", "P9"),
+            ("mem[1][1]", "P8"),
+            ("mem[0][0]", "P16"),
+            ("\\en! ", "P15"),
+        ];
+        let port_vec: Vec<ports::Port> = constraints
+            .iter()
+            .map(|(name, position)| {
+                let constraint = ConstraintPort { name: name.to_string(), port_name: position.to_string() };
+                ports::new_port(constraint, port_mappings.clone())
+            })
+            .collect();
+        let mut io = ports::group_ports(&port_vec, port_mappings);
+        io.sort_by(|a, b| a.io_name.cmp(&b.io_name));
+
+        let names: Vec<&str> = io.iter().map(|port| port.io_name.as_str()).collect();
+        assert_eq!(names, vec!["\\en!", "addr", "mem[0]", "mem[1]"]);
+
+        // Bits are ordered by their declared index, not by the order of the constraints
+        let addr: Vec<Option<usize>> = io[1].ports.iter().map(|port| port.index()).collect();
+        assert_eq!(addr, vec![Some(8), Some(8), Some(10), Some(12)]);
+
+        assert_eq!(
+            bus_warnings(&io),
+            vec![
+                "addr: bit(s) 8 are constrained more than once".to_string(),
+                "addr[12:8] has no pins for bit(s) 9, 11".to_string(),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_part_select_warning() {
+        let port_mappings = ports::fde_parse_ports().unwrap();
+        let constraint = ConstraintPort { name: "addr[15:8]".to_string(), port_name: "P7".to_string() };
+        let port_vec = vec![ports::new_port(constraint, port_mappings.clone())];
+        let io = ports::group_ports(&port_vec, port_mappings);
+
+        assert_eq!(
+            bus_warnings(&io),
+            vec!["addr[15:8] selects 8 bits but is constrained to the single pin P7, constrain each bit on its own".to_string()]
+        );
+    }
+}
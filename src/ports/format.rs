@@ -0,0 +1,180 @@
+/*
+ * Filename: format.rs
+ * Desciprtion: How the value of an IO port is displayed (radix, signedness, characters, fixed-point or
+ * HIGH/LOW levels), set per port from the meta.json of a project or with the `format` command
+ */
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+
+use super::IOPort;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PortFormat {
+    #[default]
+    Dec,
+    Hex,
+    Bin,
+    /// Two's complement of the width of the port
+    Signed,
+    /// One character per byte (most significant byte first)
+    Ascii,
+    /// Fixed-point with `frac` fractional bits, two's complement if `signed`
+    Fixed { frac: u32, signed: bool },
+    /// HIGH/LOW, single bit ports only
+    Level,
+}
+
+impl FromStr for PortFormat {
+    type Err = String;
+
+    /// Parses `dec`, `hex`, `bin`, `signed`, `ascii`, `level`, `fixed:<frac>` or `sfixed:<frac>`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let lowered = s.trim().to_lowercase();
+        let format = match lowered.as_str() {
+            "dec" | "decimal" => PortFormat::Dec,
+            "hex" => PortFormat::Hex,
+            "bin" | "binary" => PortFormat::Bin,
+            "signed" => PortFormat::Signed,
+            "ascii" | "char" => PortFormat::Ascii,
+            "level" | "highlow" => PortFormat::Level,
+            _ => {
+                let (signed, frac) = match lowered.split_once(':') {
+                    Some(("fixed", frac)) => (false, frac),
+                    Some(("sfixed", frac)) => (true, frac),
+                    _ => return Err(format!("unknown format \"{}\" (dec, hex, bin, signed, ascii, level, fixed:<n>, sfixed:<n>)", s)),
+                };
+                let frac = frac.parse().map_err(|_| format!("invalid number of fractional bits \"{}\"", frac))?;
+                PortFormat::Fixed { frac, signed }
+            }
+        };
+        Ok(format)
+    }
+}
+
+impl fmt::Display for PortFormat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            PortFormat::Dec => write!(f, "dec"),
+            PortFormat::Hex => write!(f, "hex"),
+            PortFormat::Bin => write!(f, "bin"),
+            PortFormat::Signed => write!(f, "signed"),
+            PortFormat::Ascii => write!(f, "ascii"),
+            PortFormat::Fixed { frac, signed: false } => write!(f, "fixed:{}", frac),
+            PortFormat::Fixed { frac, signed: true } => write!(f, "sfixed:{}", frac),
+            PortFormat::Level => write!(f, "level"),
+        }
+    }
+}
+
+/// Sign extends a value of `width` bits.
+fn to_signed(value: u64, width: usize) -> i64 {
+    if width == 0 || width >= 64 {
+        return value as i64;
+    }
+    let shift = 64 - width as u32;
+    ((value << shift) as i64) >> shift
+}
+
+impl PortFormat {
+    /// Whether the format can be used for a port of `width` bits.
+    pub fn check_width(&self, width: usize) -> Result<(), String> {
+        match *self {
+            PortFormat::Level if width != 1 => Err("level (HIGH/LOW) is only for single bit ports".to_string()),
+            PortFormat::Fixed { frac, .. } if frac as usize > width => {
+                Err(format!("{} fractional bits do not fit in {} bit(s)", frac, width))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Formats a value of a port of `width` bits.
+    pub fn render(&self, value: u64, width: usize) -> String {
+        let width = width.clamp(1, 64);
+        match *self {
+            PortFormat::Dec => value.to_string(),
+            PortFormat::Hex => format!("0x{:0digits$x}", value, digits = width.div_ceil(4)),
+            PortFormat::Bin => format!("0b{:0width$b}", value, width = width),
+            PortFormat::Signed => to_signed(value, width).to_string(),
+            PortFormat::Ascii => {
+                let chars: String = (0..width.div_ceil(8))
+                    .rev()
+                    .map(|byte| (value >> (8 * byte)) as u8)
+                    .map(|byte| match byte {
+                        0x20..=0x7e => (byte as char).to_string(),
+                        _ => format!("\\x{:02x}", byte),
+                    })
+                    .collect();
+                format!("'{}'", chars)
+            }
+            PortFormat::Fixed { frac, signed } => {
+                let raw = if signed { to_signed(value, width) as f64 } else { value as f64 };
+                format!("{}", raw / 2f64.powi(frac as i32))
+            }
+            PortFormat::Level if value & 0x1 == 1 => "HIGH".to_string(),
+            PortFormat::Level => "LOW".to_string(),
+        }
+    }
+}
+
+impl IOPort {
+    /// The current value of the port in its display format.
+    pub fn formatted(&self) -> String {
+        self.format.render(self.data, self.width())
+    }
+
+    /// Changes the display format of the port.
+    pub fn set_format(&mut self, format: PortFormat) -> Result<(), String> {
+        format.check_width(self.width()).map_err(|e| format!("{}: {}", self.io_name, e))?;
+        self.format = format;
+        Ok(())
+    }
+}
+
+/// Applies the formats of a project (port name -> format) to its IO ports, returning what could not be applied.
+pub fn apply_formats(io: &mut [IOPort], formats: &BTreeMap<String, String>) -> Vec<String> {
+    let mut errors = Vec::new();
+    for (name, format) in formats {
+        let Some(port) = super::find_port_mut(io, name) else {
+            errors.push(format!("\"{}\" is not a port of the project", name));
+            continue;
+        };
+        if let Err(e) = format.parse().and_then(|format| port.set_format(format)) {
+            errors.push(e);
+        }
+    }
+    errors
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_format() {
+        assert_eq!("HEX".parse(), Ok(PortFormat::Hex));
+        assert_eq!("fixed:4".parse(), Ok(PortFormat::Fixed { frac: 4, signed: false }));
+        assert_eq!("sfixed:2".parse::<PortFormat>().unwrap().to_string(), "sfixed:2");
+        assert!("octal".parse::<PortFormat>().is_err());
+        assert!("fixed:a".parse::<PortFormat>().is_err());
+    }
+
+    #[test]
+    fn test_render() {
+        assert_eq!(PortFormat::Dec.render(200, 8), "200");
+        assert_eq!(PortFormat::Hex.render(0xa, 12), "0x00a");
+        assert_eq!(PortFormat::Bin.render(0b101, 4), "0b0101");
+        assert_eq!(PortFormat::Signed.render(0xff, 8), "-1");
+        assert_eq!(PortFormat::Signed.render(0x7f, 8), "127");
+        assert_eq!(PortFormat::Ascii.render(0x41, 8), "'A'");
+        assert_eq!(PortFormat::Ascii.render(0x4107, 16), "'A\\x07'");
+        assert_eq!(PortFormat::Fixed { frac: 4, signed: false }.render(0x28, 8), "2.5");
+        assert_eq!(PortFormat::Fixed { frac: 4, signed: true }.render(0xf8, 8), "-0.5");
+        assert_eq!(PortFormat::Level.render(1, 1), "HIGH");
+        assert_eq!(PortFormat::Level.render(0, 1), "LOW");
+
+        assert!(PortFormat::Level.check_width(8).is_err());
+        assert!(PortFormat::Fixed { frac: 9, signed: false }.check_width(8).is_err());
+    }
+}
@@ -0,0 +1,122 @@
+/*
+ * Filename: frame.rs
+ * Desciprtion: The 64-bit pin vector of one clock cycle & its encoding to/from the VeriComm FIFO words
+ */
+
+use std::fmt;
+
+use super::{IOPort, IOType};
+
+/// Number of 16-bit FIFO words a frame is sent/received as.
+pub const WORDS_PER_FRAME: usize = 4;
+
+/// Bit offset of FIFO word `word` within a frame. This is the only place the word order is defined:
+/// the board sends & receives the least significant word first (word 0 carries pins 0-15).
+const fn word_shift(word: usize) -> usize {
+    16 * word
+}
+
+/// The value of every VeriComm pin during one clock cycle, bit `i` is pin index `i` of the port map.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Frame(pub u64);
+
+impl Frame {
+    /// The frame that drives the current value of every input port.
+    pub fn from_io(io: &[IOPort]) -> Self {
+        Self(
+            io.iter()
+                .filter(|port| matches!(port.io_type, IOType::INPUT))
+                .fold(0u64, |frame, port| frame | port.get_write()),
+        )
+    }
+
+    /// Decodes the FIFO words of a single frame (exactly `WORDS_PER_FRAME` words).
+    pub fn from_words(words: &[u16]) -> Self {
+        assert_eq!(words.len(), WORDS_PER_FRAME);
+        Self(
+            words
+                .iter()
+                .enumerate()
+                .fold(0u64, |frame, (i, &word)| frame | (word as u64) << word_shift(i)),
+        )
+    }
+
+    /// Encodes the frame as FIFO words, in the order they are sent to the board.
+    pub fn to_words(self) -> [u16; WORDS_PER_FRAME] {
+        let mut words = [0u16; WORDS_PER_FRAME];
+        for (i, word) in words.iter_mut().enumerate() {
+            *word = (self.0 >> word_shift(i)) as u16;
+        }
+        words
+    }
+
+    pub fn bits(self) -> u64 {
+        self.0
+    }
+}
+
+impl fmt::Display for Frame {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:#018x}", self.0)
+    }
+}
+
+/// Encodes frames into a tx buffer.
+pub fn encode(frames: &[Frame]) -> Vec<u16> {
+    frames.iter().flat_map(|frame| frame.to_words()).collect()
+}
+
+/// Decodes a rx buffer into frames, a trailing partial frame is ignored.
+pub fn decode(words: &[u16]) -> Vec<Frame> {
+    words.chunks_exact(WORDS_PER_FRAME).map(Frame::from_words).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::{find_port_mut, recipe_io};
+
+    #[test]
+    fn test_word_order() {
+        let frame = Frame(0x0036_1234_abcd_1400);
+        assert_eq!(frame.to_words(), [0x1400, 0xabcd, 0x1234, 0x0036]);
+        assert_eq!(Frame::from_words(&frame.to_words()), frame);
+    }
+
+    #[test]
+    fn test_encode_decode() {
+        let frames = [Frame(0x600), Frame(0x200), Frame(0xc01), Frame(u64::MAX)];
+        let words = encode(&frames);
+        assert_eq!(words.len(), frames.len() * WORDS_PER_FRAME);
+        assert_eq!(&words[..4], &[0x600, 0x0, 0x0, 0x0]);
+        assert_eq!(decode(&words), frames);
+
+        // Partial frames are dropped
+        assert_eq!(decode(&words[..6]), vec![Frame(0x600)]);
+        assert!(encode(&[]).is_empty());
+    }
+
+    #[test]
+    fn test_afifo_round_trip() {
+        let mut io = recipe_io("afifo_test");
+
+        // i_wdata -> pins 0-7, nReset -> pin 10, i_wr -> pin 11
+        find_port_mut(&mut io, "i_wdata").unwrap().set_value(0xa5).unwrap();
+        find_port_mut(&mut io, "nReset").unwrap().set_value(1).unwrap();
+        find_port_mut(&mut io, "i_wr").unwrap().set_value(1).unwrap();
+        let tx = Frame::from_io(&io);
+        assert_eq!(tx, Frame(0xca5));
+        assert_eq!(tx.to_words(), [0x0ca5, 0x0, 0x0, 0x0]);
+        assert_eq!(decode(&encode(&[tx])), vec![tx]);
+
+        // o_rdata <- pins 0-7, o_rempty <- pin 8, o_wfull <- pin 9
+        let rx = decode(&[0x015a, 0x0, 0x0, 0x0])[0];
+        for port in io.iter_mut().filter(|port| matches!(port.io_type, IOType::OUTPUT)) {
+            port.update(rx.bits());
+        }
+        let value = |name: &str| io.iter().find(|port| port.io_name == name).unwrap().data;
+        assert_eq!(value("o_rdata"), 0x5a);
+        assert_eq!(value("o_rempty"), 1);
+        assert_eq!(value("o_wfull"), 0);
+    }
+}
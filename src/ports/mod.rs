@@ -1,17 +1,21 @@
-/**
+/*
  * Filename: mod.rs
  * Desciprtion: The entry folder that contains all the main components for "port" functionality
  */
 
 use std::collections::HashMap;
 use anyhow::Result;
-use regex::Regex;
 use tabled::Tabled;
 use std::fmt;
 
+pub mod bus;
+pub mod format;
+pub mod frame;
 mod parse;
 pub mod table;
 
+pub use parse::PortMappings;
+
 #[derive(Debug, Clone)]
 pub struct ConstraintPort {
     pub name: String,
@@ -19,6 +23,7 @@ pub struct ConstraintPort {
 }
 
 #[derive(Debug, Clone, Tabled)]
+#[allow(clippy::upper_case_acronyms)]
 pub enum IOType { INPUT, OUTPUT, DC }   // DC -> Don't Care
 impl fmt::Display for IOType {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
@@ -35,7 +40,19 @@ pub struct Port {
     name: String,
     pin_name: String,
     pin_index: i32,
-    value: bool
+    value: bool,
+    /// Declared bit index in the bus the port belongs to (`data[3]` -> 3)
+    index: Option<usize>,
+}
+
+impl Port {
+    pub fn index(&self) -> Option<usize> {
+        self.index
+    }
+
+    pub fn pin_name(&self) -> &str {
+        &self.pin_name
+    }
 }
 
 /// Given a port constraint (a port entity read from the contraints .xml file),
@@ -52,11 +69,13 @@ pub fn new_port(constraint_p: ConstraintPort, port_mapping: parse::PortMappings)
         -1
     };
 
-    return Port {
+    let index = bus::parse_port_name(&constraint_p.name).index;
+    Port {
         name: constraint_p.name,
         pin_name: constraint_p.port_name,
-        pin_index: pin_index,
-        value: false
+        pin_index,
+        value: false,
+        index,
     }
 }
 
@@ -65,31 +84,72 @@ pub struct IOPort {
     pub io_type: IOType,
     pub io_name: String,
     pub ports: Vec<Port>,
-    pub data: u64
+    pub data: u64,
+    /// How the value is displayed
+    pub format: format::PortFormat,
+    /// Declared range of a bus, from the highest to the lowest constrained index (`[hi:lo]`) when `None`
+    pub declared: Option<bus::Range>,
+    /// Board peripheral(s) the pins are wired to (see `board::BoardProfile::label`)
+    pub peripheral: Option<String>,
 }
 
 impl IOPort {
     pub fn new(io_type: IOType, io_name: String, ports: Vec<Port>) -> Self {
-        Self { io_type, io_name , ports, data: 0u64 }
+        Self { io_type, io_name , ports, data: 0u64, format: format::PortFormat::default(), declared: None, peripheral: None }
     }
 
     /// Returns a u64 decimal of the data represented by the port(s)
+    #[allow(dead_code)]
     pub fn get_value(&self) -> u64 {
-        return self.data;
+        self.data
+    }
+
+    /// The range of the bus, declared or from the highest to the lowest constrained index.
+    pub fn range(&self) -> bus::Range {
+        self.declared.unwrap_or_else(|| {
+            let indices = self.ports.iter().filter_map(|port| port.index);
+            let (lsb, msb) = indices.fold((usize::MAX, 0), |(low, high), index| (low.min(index), high.max(index)));
+            if lsb > msb { bus::Range { msb: 0, lsb: 0 } } else { bus::Range { msb, lsb } }
+        })
+    }
+
+    /// Declares the range of the bus (which end is the most significant bit), every constrained index has to be in it.
+    pub fn declare(&mut self, range: bus::Range) -> Result<(), String> {
+        if let Some(index) = self.ports.iter().filter_map(|port| port.index).find(|index| !range.contains(*index)) {
+            return Err(format!("{}[{}] is outside of the declared range {}", self.io_name, index, range));
+        }
+        if range.width() > 64 {
+            return Err(format!("{}{} is wider than 64 bits", self.io_name, range));
+        }
+        self.declared = Some(range);
+        Ok(())
+    }
+
+    /// Position (in the value) of every pin of the port, from its declared index & the range of the bus.
+    /// Pins that are not on the board are left out.
+    fn positions(&self) -> Vec<Option<usize>> {
+        let range = self.range();
+        self.ports
+            .iter()
+            .map(|port| match port.index {
+                _ if port.pin_index == -1 => None,
+                Some(index) => range.position(index).filter(|position| *position < 64),
+                None => Some(0),
+            })
+            .collect()
     }
 
     /// Change the value represented by the ports (update/mutate),
     /// the boolean value of each individual port will be updated too.
     pub fn change_value(&mut self, new_data: u64) {
         self.data = 0u64;
-        let base = self.ports[0].pin_index;
 
-        for port in self.ports.iter_mut() {
-            if port.pin_index == -1 { continue; }
-            let pin = port.pin_index - base;
+        let positions = self.positions();
+        for (port, position) in self.ports.iter_mut().zip(positions) {
+            let Some(position) = position else { continue; };
             // Update port value
-            port.value = ((new_data >> (pin)) & 0x1) != 0;
-            self.data |= (port.value as u64) << pin;
+            port.value = ((new_data >> position) & 0x1) != 0;
+            self.data |= (port.value as u64) << position;
         }
     }
 
@@ -97,13 +157,12 @@ impl IOPort {
     /// the snapshot of one clock cycle (u64).
     pub fn update(&mut self, bitstream: u64) {
         self.data = 0u64;
-        let base = self.ports[0].pin_index;
 
-        for port in self.ports.iter_mut() {
-            if port.pin_index == -1 { continue; }
-            let pin = port.pin_index - base;
+        let positions = self.positions();
+        for (port, position) in self.ports.iter_mut().zip(positions) {
+            let Some(position) = position else { continue; };
             port.value = ((bitstream >> (port.pin_index)) & 0x1) != 0;
-            self.data |= (port.value as u64) << pin;
+            self.data |= (port.value as u64) << position;
         }
     }
 
@@ -120,11 +179,50 @@ impl IOPort {
             let pin = port.pin_index;
             temp |= (port.value as u64 & 0x1) << pin;
         }
-        return temp;
+        temp
     }
+
+    /// Number of bits of the value, the width of the range of the bus.
+    pub fn width(&self) -> usize {
+        self.range().width()
+    }
+
+    /// Drives a new value on an input port, the value has to fit in the width of the port.
+    pub fn set_value(&mut self, value: u64) -> Result<(), String> {
+        if !matches!(self.io_type, IOType::INPUT) {
+            return Err(format!("{} is not an input port", self.io_name));
+        }
+        if self.width() < 64 && value >> self.width() != 0 {
+            return Err(format!("{:#x} does not fit in {} ({} bit(s))", value, self.io_name, self.width()));
+        }
+        self.change_value(value);
+        Ok(())
+    }
+}
+
+/// Looks up a port by name, an exact match is preferred over a case-insensitive one.
+pub fn find_port_mut<'a>(io: &'a mut [IOPort], name: &str) -> Option<&'a mut IOPort> {
+    let index = io
+        .iter()
+        .position(|port| port.io_name == name)
+        .or_else(|| io.iter().position(|port| port.io_name.eq_ignore_ascii_case(name)))?;
+    io.get_mut(index)
 }
 
-/// Groups ports that match the 1D array pattern into a vector of IO_Port.
+/// Parses a port value given in hex (`0x3`), binary (`0b11`) or decimal (`3`).
+pub fn parse_port_value(value: &str) -> Result<u64, String> {
+    let lowered = value.to_lowercase();
+    let parsed = if let Some(hex) = lowered.strip_prefix("0x") {
+        u64::from_str_radix(hex, 16)
+    } else if let Some(bin) = lowered.strip_prefix("0b") {
+        u64::from_str_radix(bin, 2)
+    } else {
+        lowered.parse::<u64>()
+    };
+    parsed.map_err(|_| format!("invalid value \"{}\", expected hex (0x..), binary (0b..) or decimal", value))
+}
+
+/// Groups the bits of buses (see `bus::parse_port_name`) into a vector of IO_Port, ordered by their declared index.
 /// The key for each IO_Port is the base array name. The `input_lookup` and
 /// `output_lookup` maps are used to decide the IO_Type for the grouped port.
 pub fn group_ports(
@@ -133,20 +231,16 @@ pub fn group_ports(
 ) -> Vec<IOPort> {
     let (input_lookup, output_lookup) = (port_mapping.input, port_mapping.output);
     
-    // Regex to match strings like "arr[0]", "data[15]", etc.
-    let array_re = Regex::new(r"^(\w+)\[(\d+)\]$").unwrap();
-
     // Use a temporary HashMap to group ports by base name.
     let mut groups: HashMap<String, Vec<Port>> = HashMap::new();
 
     for port in ports {
-        if let Some(caps) = array_re.captures(&port.name) {
-            let base_name = caps.get(1).unwrap().as_str().to_string();
-            // Insert the port (cloned) into the group corresponding to the base name.
-            groups.entry(base_name).or_insert_with(Vec::new).push(port.clone());
-        } else {
-            groups.entry(port.name.to_string()).or_insert_with(Vec::new).push(port.clone())
-        }
+        // Insert the port (cloned) into the group corresponding to the base name, e.g. "mem[1]" for "mem[1][3]".
+        let base_name = bus::parse_port_name(&port.name).base;
+        groups.entry(base_name).or_default().push(port.clone());
+    }
+    for group in groups.values_mut() {
+        group.sort_by_key(|port| port.index);
     }
 
     // Now create IO_Port instances from the grouped ports.
@@ -170,35 +264,29 @@ pub fn group_ports(
     io_ports
 }
 
-/// FDE board input/output pins are stored in a folder relative to the root of the project: fde/VERICOMM_MAP.json
+/// FDE board input/output pins, fde/VERICOMM_MAP.json is embedded in the binary (see `board::DEFAULT_PIN_MAP`)
 pub fn fde_parse_ports() -> Result<parse::PortMappings, Box<dyn std::error::Error>> {
-    let json_file_path = "fde/VERICOMM_MAP.json";
-
-    // Read the JSON file into a string.
-    let json_data = std::fs::read_to_string(json_file_path)
-        .expect(&format!("Failed to read JSON file at {}", json_file_path));
-
-    match parse::parse_ports(&json_data) {
-        Ok((input, output)) => { return Ok(parse::PortMappings {input, output} ); }
-        Err(e) => {
-            eprintln!("Error parsing JSON: {}", e);
-            return Err(e);
-        }
-    }
+    let (input, output) = parse::parse_ports(crate::board::DEFAULT_PIN_MAP)?;
+    Ok(parse::PortMappings { input, output })
 }
 
-/// Helper function that converts a slice of 4 u16 elements into a u64 
-/// by shifting and combining them into a u64 (input size must be a factor of 4)
-pub fn u16_4_to_u64(rx_buffer: &[u16]) -> u64 {
-    // Ensure the slice has exactly 4 elements
-    assert_eq!(rx_buffer.len(), 4);
-    let mut result = 0u64;
-    // Shift and combine the u16 elements to form a u64
-    for (i, &num) in rx_buffer.iter().enumerate() {
-        result |= (num as u64) << (16 * (3 - i)); // Shift each u16 value by 16 * (3 - i)
-    }
+/// The constraints of a recipe (recipes/<name>/<name>_cons.xml), shared by the tests.
+#[cfg(test)]
+pub(crate) fn recipe_constraints(name: &str) -> Vec<ConstraintPort> {
+    let mut reader = crate::helper::constraints::ConstraintsReader::new(&format!("recipes/{0}/{0}_cons.xml", name));
+    reader.read().expect("recipe constraints are readable");
+    reader.get_ports().clone()
+}
 
-    result
+/// The IO ports of a recipe on the FDE pin map, shared by the tests.
+#[cfg(test)]
+pub(crate) fn recipe_io(name: &str) -> Vec<IOPort> {
+    let port_mappings = fde_parse_ports().unwrap();
+    let ports: Vec<Port> = recipe_constraints(name)
+        .into_iter()
+        .map(|constraint| new_port(constraint, port_mappings.clone()))
+        .collect();
+    group_ports(&ports, port_mappings)
 }
 
 #[cfg(test)]
@@ -300,4 +388,112 @@ mod tests {
         assert_eq!(e.data, 0x28);
     }
 
+    #[test]
+    fn test_set_value() {
+        let mut io = recipe_io("afifo_test");
+
+        let wdata = find_port_mut(&mut io, "i_wdata").unwrap();
+        wdata.set_value(parse_port_value("0x03").unwrap()).unwrap();
+        assert_eq!(wdata.get_write(), 0x3);
+        assert!(wdata.set_value(0x100).is_err());
+
+        // Exact & case-insensitive lookups
+        find_port_mut(&mut io, "nReset").unwrap().set_value(1).unwrap();
+        find_port_mut(&mut io, "I_WR").unwrap().set_value(1).unwrap();
+        let frame = io.iter().fold(0u64, |frame, port| frame | port.get_write());
+        assert_eq!(frame, 0xc03);
+
+        assert!(find_port_mut(&mut io, "o_rdata").unwrap().set_value(1).is_err());
+        assert!(find_port_mut(&mut io, "missing").is_none());
+    }
+
+    #[test]
+    fn test_parse_port_value() {
+        assert_eq!(parse_port_value("0x1F"), Ok(0x1f));
+        assert_eq!(parse_port_value("0b101"), Ok(5));
+        assert_eq!(parse_port_value("12"), Ok(12));
+        assert!(parse_port_value("0xZZ").is_err());
+        assert!(parse_port_value("high").is_err());
+    }
+
+    /// lcd_db of name_display with its bits placed on the pins in the order given (bit 0 first)
+    fn lcd_db(pins: &[&str], reversed: bool) -> IOPort {
+        let port_mappings = fde_parse_ports().unwrap();
+        let mut port_vec: Vec<Port> = pins
+            .iter()
+            .enumerate()
+            .map(|(bit, pin)| {
+                let constraint = ConstraintPort { name: format!("lcd_db[{}]", bit), port_name: pin.to_string() };
+                new_port(constraint, port_mappings.clone())
+            })
+            .collect();
+        if reversed {
+            port_vec.reverse();
+        }
+        group_ports(&port_vec, port_mappings).remove(0)
+    }
+
+    #[test]
+    fn test_bus_value_from_declared_index() {
+        // Output pins: P7 -> 0, P6 -> 1, P5 -> 2, P4 -> 3, P9 -> 4, P8 -> 5, P16 -> 6, P15 -> 7, P11 -> 8, P18 -> 11
+        let shuffled = ["P15", "P16", "P18", "P4", "P11", "P7", "P6", "P9"];
+
+        // Listed MSB first or LSB first, the value only depends on the index
+        for reversed in [false, true] {
+            let mut lcd_db = lcd_db(&shuffled, reversed);
+            assert_eq!(lcd_db.width(), 8);
+
+            // Only bit 2 (P18, pin 11) & bit 7 (P9, pin 4) are set
+            lcd_db.update((1 << 11) | (1 << 4));
+            assert_eq!(lcd_db.data, 0b1000_0100);
+
+            lcd_db.change_value(0b0000_0011);
+            assert_eq!(lcd_db.get_write(), (1 << 7) | (1 << 6));
+        }
+
+        // The pins of name_display are contiguous, the same value either way
+        let mut lcd_db = lcd_db(&["P7", "P6", "P5", "P4", "P9", "P8", "P16", "P15"], false);
+        lcd_db.update(0x28);
+        assert_eq!(lcd_db.data, 0x28);
+    }
+
+    #[test]
+    fn test_declared_range() {
+        let mut lcd_db = lcd_db(&["P7", "P6", "P5", "P4", "P9", "P8", "P16", "P15"], false);
+
+        // [0:7]: lcd_db[0] is the MSB
+        lcd_db.declare(bus::Range::parse("[0:7]").unwrap()).unwrap();
+        lcd_db.update(0x01);
+        assert_eq!(lcd_db.data, 0x80);
+
+        // Every constrained index has to be in the range, a wider one leaves the missing bits at 0
+        assert!(lcd_db.declare(bus::Range { msb: 11, lsb: 4 }).is_err());
+        lcd_db.declare(bus::Range::parse("11:0").unwrap()).unwrap();
+        assert_eq!(lcd_db.width(), 12);
+        lcd_db.update(0x03);
+        assert_eq!(lcd_db.data, 0x03);
+        assert_eq!(bus::bus_warnings(&[lcd_db]), vec!["lcd_db[11:0] has no pins for bit(s) 8-11".to_string()]);
+
+        assert!(bus::Range::parse("[7]").is_err());
+    }
+
+    #[test]
+    fn test_apply_formats() {
+        let mut io = recipe_io("afifo_test");
+        let formats = [("o_rdata", "hex"), ("o_rempty", "level"), ("i_wdata", "level"), ("missing", "bin")]
+            .iter()
+            .map(|(port, format)| (port.to_string(), format.to_string()))
+            .collect();
+
+        // i_wdata is 8 bits wide & missing is not a port
+        assert_eq!(format::apply_formats(&mut io, &formats).len(), 2);
+
+        let rdata = find_port_mut(&mut io, "o_rdata").unwrap();
+        rdata.update(0x3);
+        assert_eq!(rdata.formatted(), "0x03");
+        let rempty = find_port_mut(&mut io, "o_rempty").unwrap();
+        rempty.update(0x100);
+        assert_eq!(rempty.formatted(), "HIGH");
+        assert_eq!(find_port_mut(&mut io, "i_wdata").unwrap().formatted(), "0");
+    }
 }
\ No newline at end of file
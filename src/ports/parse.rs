@@ -1,11 +1,10 @@
-/**
+/*
  * Filename: parse.rs
  * Desciprtion: Helper functions for parsing the constraints (.xml) file
  */
 
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use serde_json;
 use anyhow::Result;
 
 // Define a struct that matches the JSON structure.
@@ -15,8 +14,11 @@ pub struct PortMappings {
     pub output: HashMap<String, i32>,
 }
 
+// Pin name -> index in the frame
+type PinIndices = HashMap<String, i32>;
+
 // Helper method to parse the JSON string into input_ports and output_ports.
-pub fn parse_ports(json_str: &str) -> Result<(HashMap<String, i32>, HashMap<String, i32>), Box<dyn std::error::Error>> {
+pub fn parse_ports(json_str: &str) -> Result<(PinIndices, PinIndices), Box<dyn std::error::Error>> {
     // Deserialize the JSON into our PortMappings struct.
     let mappings: PortMappings = serde_json::from_str(json_str)?;
     
@@ -1,9 +1,9 @@
-/**
+/*
  * Filename: table.rs
  * Desciprtion: A helper class that outputs a "printable" table for the Tabled library
  */
 
-use tabled::Tabled;
+use tabled::{Table, Tabled, settings::{Remove, location::ByColumnName}};
 
 use super::{IOType, IOPort};
 
@@ -11,16 +11,25 @@ use super::{IOType, IOPort};
 pub struct IOPortsTable<'a> {
     io_type: &'a IOType,
     port_name: &'a str,
-    data: u64,
+    data: String,
+    peripheral: String,
 }
 
 impl<'a> IOPortsTable<'a> {
-  pub fn from_io(io_ports: &'a Vec<IOPort>) -> Vec<Self> {
+  pub fn from_io(io_ports: &'a [IOPort]) -> Vec<Self> {
     let mut ports: Vec<Self> = Vec::new();
     for port in io_ports.iter() {
       if let IOType::DC = port.io_type { continue; }
-      ports.push(Self { io_type: &port.io_type, port_name: &port.io_name, data: port.data})
+      ports.push(Self { io_type: &port.io_type, port_name: &port.io_name, data: port.formatted(), peripheral: port.peripheral.clone().unwrap_or_default() })
     }
-    return ports;
+    ports
   }
-}
\ No newline at end of file
+}
+
+/// Leaves the `peripheral` column out of a table of ports while none of them is wired to a peripheral,
+/// the built-in FDE profile documents none but its clock pins.
+pub fn hide_peripherals(table: &mut Table, io: &[IOPort]) {
+  if io.iter().all(|port| port.peripheral.is_none()) {
+    table.with(Remove::column(ByColumnName::new("peripheral")));
+  }
+}
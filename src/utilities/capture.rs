@@ -0,0 +1,66 @@
+/*
+ * Filename: capture.rs
+ * Desciprtion: A recorded run, the frames driven on & sampled from the board every cycle
+ */
+
+use crate::ports::{frame::Frame, IOPort, IOType};
+
+/// Inputs as driven & outputs as sampled during one cycle.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Sample {
+    pub tx: Frame,
+    pub rx: Frame,
+}
+
+/// A recorded run of the loaded project, kept as the last capture for the other views/exports.
+#[derive(Debug, Clone)]
+pub struct Capture {
+    /// The IO ports of the project the capture was taken with (values are not used)
+    pub io: Vec<IOPort>,
+    pub samples: Vec<Sample>,
+}
+
+impl Capture {
+    pub fn new(io: &[IOPort]) -> Self {
+        Self { io: io.to_vec(), samples: Vec::new() }
+    }
+
+    pub fn push(&mut self, tx: Frame, rx: Frame) {
+        self.samples.push(Sample { tx, rx });
+    }
+
+    pub fn len(&self) -> usize {
+        self.samples.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.samples.is_empty()
+    }
+
+    /// The ports that are recorded (don't care ports are left out), sorted by name.
+    pub fn ports(&self) -> Vec<&IOPort> {
+        let mut ports: Vec<&IOPort> = self
+            .io
+            .iter()
+            .filter(|port| !matches!(port.io_type, IOType::DC))
+            .collect();
+        ports.sort_by(|a, b| a.io_name.cmp(&b.io_name));
+        ports
+    }
+
+    /// The value of a port in every cycle, inputs from the driven frames & outputs from the sampled ones.
+    pub fn values(&self, port: &IOPort) -> Vec<u64> {
+        let mut port = port.clone();
+        self.samples
+            .iter()
+            .map(|sample| {
+                let frame = match port.io_type {
+                    IOType::INPUT => sample.tx,
+                    _ => sample.rx,
+                };
+                port.update(frame.bits());
+                port.data
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,49 @@
+/*
+ * Filename: clock.rs
+ * Desciprtion: The VeriComm design clock as stored in the configuration space (clock high & low delays)
+ */
+
+use crate::helper::smims_cfg::CfgField;
+use crate::vlfd::cfg::CfgInfo;
+
+// Neither the clock of the SMIMS engine nor how the delays divide it are documented for the FDE board
+// (the vlfd driver only exposes the raw delays), so the design clock is reported as the delays only.
+
+/// The clock high & low delays, as stored in the configuration space.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClockSetting {
+    pub high_delay: u16,
+    pub low_delay: u16,
+}
+
+impl ClockSetting {
+    pub fn from_cfg(cfg: &impl CfgInfo) -> Self {
+        Self {
+            high_delay: CfgField::ClockHighDelay.read(cfg),
+            low_delay: CfgField::ClockLowDelay.read(cfg),
+        }
+    }
+}
+
+/// Formats a frequency with the largest fitting unit, e.g. `1.500 MHz`.
+pub fn format_frequency(hz: f64) -> String {
+    if hz >= 1e6 {
+        format!("{:.3} MHz", hz / 1e6)
+    } else if hz >= 1e3 {
+        format!("{:.3} kHz", hz / 1e3)
+    } else {
+        format!("{:.3} Hz", hz)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_frequency() {
+        assert_eq!(format_frequency(30_000_000.0), "30.000 MHz");
+        assert_eq!(format_frequency(32_768.0), "32.768 kHz");
+        assert_eq!(format_frequency(1.0), "1.000 Hz");
+    }
+}
@@ -0,0 +1,430 @@
+/*
+ * Filename: decode.rs
+ * Desciprtion: Protocol decoders (UART, SPI & I2C) for the signals of a capture, one sample per
+ * design clock cycle, into a list of annotated transactions that can be exported as CSV
+ */
+
+use std::fs;
+
+use tabled::Tabled;
+
+use super::capture::Capture;
+use crate::ports::bus;
+
+/// A decoded byte, word or bus condition, from the cycle it starts in to the one it ends in.
+#[derive(Debug, Clone, PartialEq, Eq, Tabled)]
+pub struct Transaction {
+    pub start: usize,
+    pub end: usize,
+    pub kind: String,
+    pub data: String,
+    pub note: String,
+}
+
+impl Transaction {
+    fn new(start: usize, end: usize, kind: &str, data: String, note: String) -> Self {
+        Self { start, end, kind: kind.to_string(), data, note }
+    }
+}
+
+/// The values of a single bit signal of a capture: a single bit port or one bit of a bus (`data[3]`).
+pub fn signal(capture: &Capture, name: &str) -> Result<Vec<bool>, String> {
+    let find = |name: &str| capture.io.iter().find(|port| port.io_name == name || port.io_name.eq_ignore_ascii_case(name));
+    let (port, position) = match find(name) {
+        Some(port) if port.width() == 1 => (port, 0),
+        Some(port) => return Err(format!("{} is {} bits, pick one of them (e.g. {}[0])", port.io_name, port.width(), port.io_name)),
+        None => {
+            let port_name = bus::parse_port_name(name);
+            let port = find(&port_name.base).ok_or(format!("\"{}\" is not a port of the capture", name))?;
+            let position = port_name
+                .index
+                .and_then(|index| port.range().position(index))
+                .ok_or(format!("\"{}\" is not a bit of {}{}", name, port.io_name, port.range()))?;
+            (port, position)
+        }
+    };
+    Ok(capture.values(port).iter().map(|value| value >> position & 0x1 == 1).collect())
+}
+
+/// A byte as hex & as a character when printable.
+fn byte_note(byte: u64) -> String {
+    match byte {
+        0x20..=0x7e => format!("'{}'", byte as u8 as char),
+        _ => String::new(),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Parity {
+    Even,
+    Odd,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UartConfig {
+    /// Baud rate in design clock cycles per bit
+    pub cycles_per_bit: usize,
+    pub data_bits: usize,
+    pub parity: Option<Parity>,
+}
+
+impl UartConfig {
+    pub fn new(cycles_per_bit: usize) -> Self {
+        Self { cycles_per_bit, data_bits: 8, parity: None }
+    }
+}
+
+/// Decodes a UART line (idle high, start bit, data bits LSB first, optional parity, a stop bit),
+/// sampling every bit in its middle.
+pub fn uart(line: &[bool], config: &UartConfig) -> Result<Vec<Transaction>, String> {
+    if config.cycles_per_bit < 2 {
+        return Err("at least 2 cycles per bit are needed".to_string());
+    }
+    if !(5..=9).contains(&config.data_bits) {
+        return Err(format!("{} data bits, expected 5 to 9", config.data_bits));
+    }
+
+    let bit = config.cycles_per_bit;
+    let frame_bits = 1 + config.data_bits + config.parity.is_some() as usize + 1;
+    let sample = |start: usize, n: usize| line.get(start + n * bit + bit / 2).copied();
+
+    let mut transactions = Vec::new();
+    let mut cycle = 1;
+    while cycle < line.len() {
+        // A start bit is a falling edge that is still low in the middle of the bit
+        let falling = line[cycle - 1] && !line[cycle];
+        if !falling || sample(cycle, 0) != Some(false) {
+            cycle += 1;
+            continue;
+        }
+        let Some(stop) = sample(cycle, frame_bits - 1) else {
+            transactions.push(Transaction::new(cycle, line.len() - 1, "partial", String::new(), "capture ended".to_string()));
+            break;
+        };
+
+        let bits: Vec<bool> = (1..=config.data_bits).filter_map(|n| sample(cycle, n)).collect();
+        let value = bits.iter().rev().fold(0u64, |value, bit| value << 1 | *bit as u64);
+        let mut notes = vec![byte_note(value)];
+        if let Some(parity) = config.parity {
+            // The data bits & the parity bit have an even (odd) number of ones
+            let parity_bit = sample(cycle, 1 + config.data_bits) == Some(true);
+            let ones = bits.iter().filter(|bit| **bit).count() + parity_bit as usize;
+            if (ones % 2 == 1) != (parity == Parity::Odd) {
+                notes.push("parity error".to_string());
+            }
+        }
+        if !stop {
+            notes.push("framing error".to_string());
+        }
+        let end = cycle + frame_bits * bit - 1;
+        notes.retain(|note| !note.is_empty());
+        transactions.push(Transaction::new(cycle, end.min(line.len() - 1), "byte", format!("{:#04x}", value), notes.join(", ")));
+        // The stop bit is high, look for the next start bit after its middle
+        cycle += (frame_bits - 1) * bit + bit / 2 + 1;
+    }
+    Ok(transactions)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SpiConfig {
+    /// Clock polarity, the level of SCLK when idle
+    pub cpol: bool,
+    /// Clock phase, data is sampled on the trailing (second) edge when set
+    pub cpha: bool,
+    pub word_bits: usize,
+    pub lsb_first: bool,
+}
+
+impl SpiConfig {
+    /// SPI mode 0 to 3 (CPOL << 1 | CPHA), 8 bit words MSB first.
+    pub fn mode(mode: u8) -> Result<Self, String> {
+        if mode > 3 {
+            return Err(format!("invalid SPI mode {}, expected 0 to 3", mode));
+        }
+        Ok(Self { cpol: mode & 0x2 != 0, cpha: mode & 0x1 != 0, word_bits: 8, lsb_first: false })
+    }
+}
+
+/// SPI signals, MISO & the (active low) chip select are optional.
+pub struct SpiSignals<'a> {
+    pub sclk: &'a [bool],
+    pub mosi: &'a [bool],
+    pub miso: Option<&'a [bool]>,
+    pub cs: Option<&'a [bool]>,
+}
+
+/// Decodes SPI words, sampled on the rising edge of SCLK when CPOL == CPHA & on the falling edge otherwise.
+/// Bits are counted from the start of the capture, or from every assertion of the chip select.
+pub fn spi(signals: &SpiSignals, config: &SpiConfig) -> Vec<Transaction> {
+    let sample_on_rise = config.cpol == config.cpha;
+    let word = |bits: &[bool]| {
+        let bits: Vec<&bool> = if config.lsb_first { bits.iter().rev().collect() } else { bits.iter().collect() };
+        bits.iter().fold(0u64, |value, bit| value << 1 | **bit as u64)
+    };
+    let digits = config.word_bits.div_ceil(4) + 2;
+
+    let mut transactions = Vec::new();
+    let (mut mosi, mut miso): (Vec<bool>, Vec<bool>) = (Vec::new(), Vec::new());
+    let mut start = 0;
+    let finish = |transactions: &mut Vec<Transaction>, kind: &str, start: usize, end: usize, mosi: &[bool], miso: &[bool]| {
+        let note = if signals.miso.is_some() { format!("miso {:#0digits$x}", word(miso)) } else { String::new() };
+        transactions.push(Transaction::new(start, end, kind, format!("{:#0digits$x}", word(mosi)), note));
+    };
+
+    for cycle in 1..signals.sclk.len() {
+        let selected = signals.cs.is_none_or(|cs| !cs[cycle]);
+        if !selected {
+            // Deasserting the chip select ends the word early
+            if !mosi.is_empty() {
+                finish(&mut transactions, "partial", start, cycle - 1, &mosi, &miso);
+                mosi.clear();
+                miso.clear();
+            }
+            continue;
+        }
+
+        let (before, now) = (signals.sclk[cycle - 1], signals.sclk[cycle]);
+        let edge = if sample_on_rise { !before && now } else { before && !now };
+        if !edge {
+            continue;
+        }
+        if mosi.is_empty() {
+            start = cycle;
+        }
+        mosi.push(signals.mosi[cycle]);
+        miso.push(signals.miso.is_some_and(|miso| miso[cycle]));
+        if mosi.len() == config.word_bits {
+            finish(&mut transactions, "word", start, cycle, &mosi, &miso);
+            mosi.clear();
+            miso.clear();
+        }
+    }
+    if !mosi.is_empty() {
+        finish(&mut transactions, "partial", start, signals.sclk.len() - 1, &mosi, &miso);
+    }
+    transactions
+}
+
+/// Decodes I2C: START & STOP conditions (SDA changing while SCL is high), the address byte after a START
+/// (7-bit address & read/write) and data bytes, each followed by the ACK/NACK bit, sampled on the rising edge of SCL.
+pub fn i2c(scl: &[bool], sda: &[bool]) -> Vec<Transaction> {
+    let mut transactions = Vec::new();
+    // Bits of the current byte (with the ACK bit) & whether it is the first byte after a START
+    let mut bits: Vec<bool> = Vec::new();
+    let mut start = 0;
+    let mut address = false;
+    let mut active = false;
+
+    for cycle in 1..scl.len().min(sda.len()) {
+        let scl_high = scl[cycle - 1] && scl[cycle];
+        if scl_high && sda[cycle - 1] != sda[cycle] {
+            // The SCL pulse before a STOP or a repeated START samples a single bit
+            if bits.len() > 1 {
+                transactions.push(Transaction::new(start, cycle - 1, "partial", String::new(), format!("{} bit(s)", bits.len())));
+            }
+            if sda[cycle - 1] {
+                let kind = if active { "restart" } else { "start" };
+                transactions.push(Transaction::new(cycle, cycle, kind, String::new(), String::new()));
+                active = true;
+                address = true;
+            } else {
+                transactions.push(Transaction::new(cycle, cycle, "stop", String::new(), String::new()));
+                active = false;
+            }
+            bits.clear();
+            continue;
+        }
+
+        let rising = !scl[cycle - 1] && scl[cycle];
+        if !active || !rising {
+            continue;
+        }
+        if bits.is_empty() {
+            start = cycle;
+        }
+        bits.push(sda[cycle]);
+        if bits.len() < 9 {
+            continue;
+        }
+
+        let byte = bits[..8].iter().fold(0u64, |value, bit| value << 1 | *bit as u64);
+        let ack = if bits[8] { "NACK" } else { "ACK" };
+        let transaction = if address {
+            let direction = if byte & 0x1 == 1 { "read" } else { "write" };
+            Transaction::new(start, cycle, "address", format!("{:#04x}", byte >> 1), format!("{}, {}", direction, ack))
+        } else {
+            let note = [byte_note(byte), ack.to_string()].iter().filter(|note| !note.is_empty()).cloned().collect::<Vec<_>>();
+            Transaction::new(start, cycle, "data", format!("{:#04x}", byte), note.join(", "))
+        };
+        transactions.push(transaction);
+        address = false;
+        bits.clear();
+    }
+    transactions
+}
+
+/// Quotes a CSV field when needed.
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+pub fn to_csv(transactions: &[Transaction]) -> String {
+    let mut csv = String::from("start,end,kind,data,note\n");
+    for transaction in transactions {
+        let fields = [
+            transaction.start.to_string(),
+            transaction.end.to_string(),
+            csv_field(&transaction.kind),
+            csv_field(&transaction.data),
+            csv_field(&transaction.note),
+        ];
+        csv.push_str(&fields.join(","));
+        csv.push('\n');
+    }
+    csv
+}
+
+pub fn save_csv(transactions: &[Transaction], path: &str) -> Result<(), String> {
+    fs::write(path, to_csv(transactions)).map_err(|e| format!("failed to write {}: {}", path, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A UART frame (8N1 or with parity) of `value` at `bit` cycles per bit, after `idle` idle cycles.
+    fn uart_frame(value: u8, bit: usize, idle: usize, parity: Option<bool>) -> Vec<bool> {
+        let mut levels = vec![true; idle];
+        let mut bits = vec![false];
+        bits.extend((0..8).map(|n| value >> n & 0x1 == 1));
+        bits.extend(parity);
+        bits.push(true);
+        for level in bits {
+            levels.extend(std::iter::repeat_n(level, bit));
+        }
+        levels
+    }
+
+    #[test]
+    fn test_uart() {
+        let mut line = uart_frame(b'H', 4, 3, None);
+        line.extend(uart_frame(0x0d, 4, 0, None));
+        line.extend(uart_frame(b'i', 4, 5, None));
+        let transactions = uart(&line, &UartConfig::new(4)).unwrap();
+
+        let decoded: Vec<(&str, &str)> = transactions.iter().map(|t| (t.data.as_str(), t.note.as_str())).collect();
+        assert_eq!(decoded, vec![("0x48", "'H'"), ("0x0d", ""), ("0x69", "'i'")]);
+        assert_eq!((transactions[0].start, transactions[0].end), (3, 42));
+
+        // Even parity: 'A' (0x41) has two ones, so the parity bit is 0
+        let config = UartConfig { parity: Some(Parity::Even), ..UartConfig::new(4) };
+        assert_eq!(uart(&uart_frame(b'A', 4, 2, Some(false)), &config).unwrap()[0].note, "'A'");
+        assert_eq!(uart(&uart_frame(b'A', 4, 2, Some(true)), &config).unwrap()[0].note, "'A', parity error");
+
+        // A low stop bit
+        let mut broken = uart_frame(b'A', 4, 2, None);
+        let len = broken.len();
+        broken[len - 4..].fill(false);
+        broken.extend([true; 4]);
+        assert_eq!(uart(&broken, &UartConfig::new(4)).unwrap()[0].note, "'A', framing error");
+
+        assert!(uart(&line, &UartConfig::new(1)).is_err());
+    }
+
+    /// SPI mode 0 (sample on the rising edge), two cycles per half period, MSB first.
+    fn spi_words(words: &[(u8, u8)]) -> (Vec<bool>, Vec<bool>, Vec<bool>, Vec<bool>) {
+        let (mut sclk, mut mosi, mut miso, mut cs) = (vec![false; 2], vec![false; 2], vec![false; 2], vec![true; 2]);
+        for (out, back) in words {
+            for n in (0..8).rev() {
+                for level in [false, false, true, true] {
+                    sclk.push(level);
+                    mosi.push(out >> n & 0x1 == 1);
+                    miso.push(back >> n & 0x1 == 1);
+                    cs.push(false);
+                }
+            }
+        }
+        for signal in [&mut sclk, &mut mosi, &mut miso] {
+            signal.extend([false; 2]);
+        }
+        cs.extend([true; 2]);
+        (sclk, mosi, miso, cs)
+    }
+
+    #[test]
+    fn test_spi() {
+        let (sclk, mosi, miso, cs) = spi_words(&[(0xa5, 0x3c), (0x01, 0xff)]);
+        let signals = SpiSignals { sclk: &sclk, mosi: &mosi, miso: Some(&miso), cs: Some(&cs) };
+        let transactions = spi(&signals, &SpiConfig::mode(0).unwrap());
+        let decoded: Vec<(&str, &str, &str)> = transactions.iter().map(|t| (t.kind.as_str(), t.data.as_str(), t.note.as_str())).collect();
+        assert_eq!(decoded, vec![("word", "0xa5", "miso 0x3c"), ("word", "0x01", "miso 0xff")]);
+
+        // Sampled on the falling edge (mode 1) the bits are shifted by one
+        let signals = SpiSignals { sclk: &sclk, mosi: &mosi, miso: None, cs: None };
+        let transactions = spi(&signals, &SpiConfig::mode(1).unwrap());
+        assert_eq!(transactions[0].data, "0x4a");
+        assert_eq!(transactions[0].note, "");
+
+        // The chip select is deasserted in the middle of a word
+        let (sclk, mosi, _, mut cs) = spi_words(&[(0xff, 0)]);
+        cs[20..].fill(true);
+        let signals = SpiSignals { sclk: &sclk, mosi: &mosi, miso: None, cs: Some(&cs) };
+        let transactions = spi(&signals, &SpiConfig::mode(0).unwrap());
+        assert_eq!(transactions.len(), 1);
+        assert_eq!((transactions[0].kind.as_str(), transactions[0].data.as_str()), ("partial", "0x0f"));
+
+        assert!(SpiConfig::mode(4).is_err());
+    }
+
+    /// An I2C transfer: START, the bytes (each with its ACK bit), STOP, with 4 cycles per SCL period.
+    fn i2c_transfer(bytes: &[(u8, bool)]) -> (Vec<bool>, Vec<bool>) {
+        let (mut scl, mut sda) = (vec![true; 2], vec![true; 2]);
+        // START: SDA falls while SCL is high
+        scl.extend([true, false]);
+        sda.extend([false, false]);
+        for (byte, nack) in bytes {
+            let bits: Vec<bool> = (0..8).rev().map(|n| byte >> n & 0x1 == 1).chain([*nack]).collect();
+            for bit in bits {
+                scl.extend([false, true, true, false]);
+                sda.extend([bit; 4]);
+            }
+        }
+        // STOP: SDA rises while SCL is high
+        scl.extend([false, true, true, true]);
+        sda.extend([false, false, true, true]);
+        (scl, sda)
+    }
+
+    #[test]
+    fn test_i2c() {
+        let (scl, sda) = i2c_transfer(&[(0x50 << 1, false), (b'K', false), (0x00, true)]);
+        let transactions = i2c(&scl, &sda);
+        let decoded: Vec<(&str, &str, &str)> = transactions.iter().map(|t| (t.kind.as_str(), t.data.as_str(), t.note.as_str())).collect();
+        assert_eq!(
+            decoded,
+            vec![
+                ("start", "", ""),
+                ("address", "0x50", "write, ACK"),
+                ("data", "0x4b", "'K', ACK"),
+                ("data", "0x00", "NACK"),
+                ("stop", "", ""),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_csv() {
+        let transactions = vec![
+            Transaction::new(0, 9, "byte", "0x2c".to_string(), "','".to_string()),
+            Transaction::new(10, 19, "byte", "0x22".to_string(), "'\"', framing error".to_string()),
+        ];
+        assert_eq!(
+            to_csv(&transactions),
+            "start,end,kind,data,note\n0,9,byte,0x2c,\"','\"\n10,19,byte,0x22,\"'\"\"', framing error\"\n"
+        );
+    }
+}
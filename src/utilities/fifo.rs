@@ -1,13 +0,0 @@
-use crate::ports::{self, IOPort};
-
-use anyhow::Result;
-
-/// AFIFO 
-pub fn get_fifo_write(data: u8, write_port: IOPort) -> u64 {
-  let mut data: u64 = 0u64;
-
-  // Double check that the port name(s) are correct
-  
-  
-  return data;
-}
\ No newline at end of file
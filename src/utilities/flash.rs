@@ -0,0 +1,104 @@
+/*
+ * Filename: flash.rs
+ * Desciprtion: The onboard flash as described by the configuration space (geometry & address ranges).
+ * The vlfd driver has no flash access (erase/read/write), so the flash can only be described
+ */
+
+use crate::helper::smims_cfg::CfgField;
+use crate::vlfd::cfg::CfgInfo;
+
+/// Flash geometry as reported by the configuration space, sizes are in 16-bit words.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FlashGeometry {
+    pub total_blocks: u32,
+    pub block_size: u32,
+    pub cluster_size: u32,
+}
+
+impl FlashGeometry {
+    pub fn from_cfg(cfg: &impl CfgInfo) -> Self {
+        Self {
+            total_blocks: CfgField::FlashTotalBlock.read(cfg) as u32,
+            block_size: CfgField::FlashBlockSize.read(cfg) as u32,
+            cluster_size: CfgField::FlashClusterSize.read(cfg) as u32,
+        }
+    }
+
+    pub fn clusters_per_block(&self) -> u32 {
+        self.block_size.checked_div(self.cluster_size).unwrap_or(0)
+    }
+
+    pub fn capacity(&self) -> u64 {
+        self.total_blocks as u64 * self.block_size as u64
+    }
+}
+
+/// A range of clusters in flash, from the first cluster to the last one (inclusive).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FlashRegion {
+    pub begin_block: u16,
+    pub begin_cluster: u16,
+    pub end_block: u16,
+    pub end_cluster: u16,
+}
+
+impl FlashRegion {
+    /// Reads the region currently configured in the configuration space.
+    pub fn from_cfg(cfg: &impl CfgInfo) -> Self {
+        Self {
+            begin_block: CfgField::FlashBeginBlockAddr.read(cfg),
+            begin_cluster: CfgField::FlashBeginClusterAddr.read(cfg),
+            end_block: CfgField::FlashReadEndBlockAddr.read(cfg),
+            end_cluster: CfgField::FlashReadEndClusterAddr.read(cfg),
+        }
+    }
+
+    /// Number of words the region spans, the addresses come from the board so they are checked
+    /// against the geometry first (the result is never larger than the flash).
+    pub fn words(&self, geometry: &FlashGeometry) -> Result<usize, String> {
+        let per_block = geometry.clusters_per_block() as u64;
+        if geometry.cluster_size == 0 || per_block == 0 {
+            return Err("the board did not report its flash geometry".to_string());
+        }
+        for (block, cluster) in [(self.begin_block, self.begin_cluster), (self.end_block, self.end_cluster)] {
+            if block as u32 >= geometry.total_blocks || cluster as u64 >= per_block {
+                return Err(format!(
+                    "block {} cluster {} is outside the flash ({} blocks of {} clusters)",
+                    block, cluster, geometry.total_blocks, per_block
+                ));
+            }
+        }
+
+        let first = self.begin_block as u64 * per_block + self.begin_cluster as u64;
+        let last = self.end_block as u64 * per_block + self.end_cluster as u64;
+        if last < first {
+            return Err(format!("the region ends (block {}) before it begins (block {})", self.end_block, self.begin_block));
+        }
+        Ok(((last - first + 1) * geometry.cluster_size as u64) as usize)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GEOMETRY: FlashGeometry = FlashGeometry { total_blocks: 4, block_size: 64, cluster_size: 16 };
+
+    #[test]
+    fn test_region_words_bounded() {
+        // Whole flash
+        let region = FlashRegion { begin_block: 0, begin_cluster: 0, end_block: 3, end_cluster: 3 };
+        assert_eq!(region.words(&GEOMETRY), Ok(256));
+
+        // Addresses past the flash, reversed ranges & an unknown geometry are rejected
+        let region = FlashRegion { begin_block: 0, begin_cluster: 0, end_block: 0xffff, end_cluster: 0xffff };
+        assert!(region.words(&GEOMETRY).is_err());
+        let region = FlashRegion { begin_block: 0, begin_cluster: 0, end_block: 0, end_cluster: 4 };
+        assert!(region.words(&GEOMETRY).is_err());
+        let region = FlashRegion { begin_block: 2, begin_cluster: 0, end_block: 1, end_cluster: 0 };
+        assert!(region.words(&GEOMETRY).is_err());
+        let unknown = FlashGeometry { total_blocks: 0, block_size: 0, cluster_size: 0 };
+        let region = FlashRegion { begin_block: 0, begin_cluster: 0, end_block: 0, end_cluster: 0 };
+        assert!(region.words(&unknown).is_err());
+    }
+}
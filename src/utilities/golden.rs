@@ -0,0 +1,198 @@
+/*
+ * Filename: golden.rs
+ * Desciprtion: Golden (expected) output values per port & cycle, with don't care bits, and the
+ * comparison of them against the sampled outputs of a run
+ */
+
+use tabled::Tabled;
+
+use crate::ports::{frame::Frame, IOPort};
+
+/// The expected value of an output port in one cycle, only the bits set in `mask` are checked.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Expected {
+    pub cycle: usize,
+    /// Index into the IO ports of the project
+    pub port: usize,
+    pub value: u64,
+    pub mask: u64,
+}
+
+impl Expected {
+    pub fn matches(&self, actual: u64) -> bool {
+        (actual ^ self.value) & self.mask == 0
+    }
+}
+
+/// Parses an expected value, binary & hex digits can be `x` (don't care), e.g. `0b1x0x` or `0x3x`.
+/// The bits above the digits are checked to be 0, a single `x` does not care about any bit.
+/// Returns (value, mask of the checked bits).
+pub fn parse_expected(value: &str) -> Result<(u64, u64), String> {
+    let invalid = || format!("invalid expected value \"{}\"", value);
+    let lowered = value.to_lowercase().replace('_', "");
+    if lowered == "x" {
+        return Ok((0, 0));
+    }
+
+    let (digits, bits) = if let Some(digits) = lowered.strip_prefix("0b") {
+        (digits, 1)
+    } else if let Some(digits) = lowered.strip_prefix("0x") {
+        (digits, 4)
+    } else {
+        let value = lowered.parse::<u64>().map_err(|_| invalid())?;
+        return Ok((value, u64::MAX));
+    };
+    if digits.is_empty() || digits.len() * bits > 64 {
+        return Err(invalid());
+    }
+
+    let digit_mask = (1u64 << bits) - 1;
+    let above = u64::MAX.checked_shl((digits.len() * bits) as u32).unwrap_or(0);
+    let (value, mask) = digits.chars().try_fold((0u64, 0u64), |(value, mask), digit| {
+        let (digit, care) = match digit {
+            'x' => (0, 0),
+            digit => (digit.to_digit(1 << bits).ok_or_else(invalid)? as u64, digit_mask),
+        };
+        Ok::<_, String>((value << bits | digit, mask << bits | care))
+    })?;
+    Ok((value, mask | above))
+}
+
+/// Formats an expected value of a port in its display format, or in binary with `x` for the don't care bits
+/// when only some of them are checked.
+pub fn format_expected(port: &IOPort, value: u64, mask: u64) -> String {
+    let width = port.width().clamp(1, 64);
+    let all = if width == 64 { u64::MAX } else { (1u64 << width) - 1 };
+    if mask & all == all {
+        return port.format.render(value & all, width);
+    }
+    let bits: String = (0..width)
+        .rev()
+        .map(|bit| match (mask >> bit & 0x1, value >> bit & 0x1) {
+            (0, _) => 'x',
+            (_, 1) => '1',
+            _ => '0',
+        })
+        .collect();
+    format!("0b{}", bits)
+}
+
+/// The bits (most significant first) in which `actual` differs from the checked bits of `expected`.
+pub fn differing_bits(expected: &Expected, actual: u64) -> Vec<usize> {
+    let differing = (actual ^ expected.value) & expected.mask;
+    (0..64).rev().filter(|bit| differing >> bit & 0x1 == 1).collect()
+}
+
+/// The value of an output port in a sampled frame.
+pub fn sampled_value(port: &IOPort, frame: Frame) -> u64 {
+    let mut port = port.clone();
+    port.update(frame.bits());
+    port.data
+}
+
+/// A row of the mismatch report.
+#[derive(Debug, Clone, PartialEq, Eq, Tabled)]
+pub struct Mismatch {
+    pub cycle: usize,
+    pub port: String,
+    pub expected: String,
+    pub actual: String,
+    #[tabled(rename = "differing bits")]
+    pub differing: String,
+}
+
+/// Compares the expected values with the sampled output frames, returning a row per failed check.
+/// Cycles that were not sampled fail.
+pub fn compare(io: &[IOPort], expected: &[Expected], outputs: &[Frame]) -> Vec<Mismatch> {
+    expected
+        .iter()
+        .filter_map(|expected| {
+            let port = &io[expected.port];
+            let actual = outputs.get(expected.cycle).map(|&frame| sampled_value(port, frame));
+            if actual.is_some_and(|actual| expected.matches(actual)) {
+                return None;
+            }
+            Some(Mismatch {
+                cycle: expected.cycle,
+                port: port.io_name.clone(),
+                expected: format_expected(port, expected.value, expected.mask),
+                actual: actual.map_or("not sampled".to_string(), |actual| port.format.render(actual, port.width())),
+                differing: actual.map_or(String::new(), |actual| {
+                    differing_bits(expected, actual)
+                        .iter()
+                        .map(|bit| bit.to_string())
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                }),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ports;
+
+    #[test]
+    fn test_parse_expected() {
+        assert_eq!(parse_expected("3"), Ok((3, u64::MAX)));
+        assert_eq!(parse_expected("0b1x0x"), Ok((0b1000, !0b0101)));
+        assert_eq!(parse_expected("0x3X"), Ok((0x30, !0xf)));
+        assert_eq!(parse_expected("0xff"), Ok((0xff, u64::MAX)));
+        assert_eq!(parse_expected("x"), Ok((0, 0)));
+        assert!(parse_expected("0b102").is_err());
+        assert!(parse_expected("0x").is_err());
+        assert!(parse_expected("1x").is_err());
+    }
+
+    #[test]
+    fn test_format_expected() {
+        let mut io = ports::recipe_io("afifo_test");
+        let o_rdata = ports::find_port_mut(&mut io, "o_rdata").unwrap();
+        assert_eq!(format_expected(o_rdata, 3, u64::MAX), "3");
+        o_rdata.format = ports::format::PortFormat::Signed;
+        assert_eq!(format_expected(o_rdata, 0xfe, u64::MAX), "-2");
+        // Don't care bits are only shown in binary
+        assert_eq!(format_expected(o_rdata, 0x30, 0xf0), "0b0011xxxx");
+
+        let o_wfull = ports::find_port_mut(&mut io, "o_wfull").unwrap();
+        assert_eq!(format_expected(o_wfull, 1, 0), "0bx");
+    }
+
+    #[test]
+    fn test_compare() {
+        let mut io = ports::recipe_io("afifo_test");
+        let o_rdata = io.iter().position(|port| port.io_name == "o_rdata").unwrap();
+        io[o_rdata].format = ports::format::PortFormat::Hex;
+        let expected = vec![
+            Expected { cycle: 0, port: o_rdata, value: 1, mask: u64::MAX },
+            Expected { cycle: 1, port: o_rdata, value: 2, mask: u64::MAX },
+            // Only the upper nibble
+            Expected { cycle: 2, port: o_rdata, value: 0x10, mask: 0xf0 },
+            Expected { cycle: 3, port: o_rdata, value: 4, mask: u64::MAX },
+        ];
+        // o_rdata is on output pins 0-7
+        let outputs = vec![Frame(0x1), Frame(0x7), Frame(0x1f)];
+
+        let mismatches = compare(&io, &expected, &outputs);
+        assert_eq!(mismatches.len(), 2);
+        assert_eq!(
+            mismatches[0],
+            Mismatch {
+                cycle: 1,
+                port: "o_rdata".to_string(),
+                expected: "0x02".to_string(),
+                actual: "0x07".to_string(),
+                differing: "2, 0".to_string(),
+            }
+        );
+        assert_eq!(mismatches[1].actual, "not sampled");
+
+        // Both sides are shown in the format of the port
+        io[o_rdata].format = ports::format::PortFormat::Ascii;
+        let expected = vec![Expected { cycle: 0, port: o_rdata, value: 0x41, mask: u64::MAX }];
+        let mismatches = compare(&io, &expected, &[Frame(0x42)]);
+        assert_eq!((mismatches[0].expected.as_str(), mismatches[0].actual.as_str()), ("'A'", "'B'"));
+    }
+}
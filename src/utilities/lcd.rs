@@ -0,0 +1,402 @@
+/*
+ * Filename: lcd.rs
+ * Desciprtion: A virtual HD44780 character LCD, the writes of a capture (latched on the falling edge of E)
+ * are run as instructions & data writes on the DDRAM/cursor state and the display is drawn in the terminal
+ */
+
+use serde::{Deserialize, Serialize};
+use tabled::Tabled;
+
+use super::capture::Capture;
+use super::decode;
+
+/// Characters per line of the DDRAM in two-line mode, the second line starts at 0x40.
+const LINE_LENGTH: usize = 40;
+const SECOND_LINE: usize = 0x40;
+/// DDRAM of the one-line mode, 0x00-0x4f.
+const ONE_LINE_LENGTH: usize = 80;
+
+fn default_data() -> String {
+    "lcd_db".to_string()
+}
+
+fn default_enable() -> String {
+    "lcd_en".to_string()
+}
+
+fn default_rs() -> Option<String> {
+    Some("lcd_rst".to_string())
+}
+
+fn default_columns() -> usize {
+    16
+}
+
+/// Which ports of a project drive the LCD (`"lcd"` in its meta.json), the defaults are the ports of name_display.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LcdMapping {
+    /// DB7-DB0 (DB7-DB4 in 4-bit mode)
+    #[serde(default = "default_data")]
+    pub data: String,
+    #[serde(default = "default_enable")]
+    pub enable: String,
+    /// Register select (high for data), `lcd_rst` of name_display. Set to `null` when the design does not drive
+    /// it, the writes are then listed without telling commands from characters
+    #[serde(default = "default_rs")]
+    pub rs: Option<String>,
+    /// Read/write, reads (high) are skipped
+    #[serde(default)]
+    pub rw: Option<String>,
+    /// Characters per line of the module
+    #[serde(default = "default_columns")]
+    pub columns: usize,
+}
+
+impl Default for LcdMapping {
+    fn default() -> Self {
+        Self {
+            data: default_data(),
+            enable: default_enable(),
+            rs: default_rs(),
+            rw: None,
+            columns: default_columns(),
+        }
+    }
+}
+
+/// A write to the LCD.
+#[derive(Debug, Clone, PartialEq, Eq, Tabled)]
+pub struct LcdEvent {
+    pub cycle: usize,
+    pub kind: &'static str,
+    #[tabled(display = "hex_byte")]
+    pub value: u8,
+    pub description: String,
+}
+
+fn hex_byte(value: &u8) -> String {
+    format!("{:#04x}", value)
+}
+
+/// The state of the HD44780.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Lcd {
+    ddram: [u8; ONE_LINE_LENGTH],
+    /// Address counter
+    address: usize,
+    /// Writes go to CGRAM (custom characters) after a "set CGRAM address"
+    cgram: bool,
+    increment: bool,
+    shift_on_write: bool,
+    /// Display shift, in characters to the left
+    shift: usize,
+    pub display_on: bool,
+    pub cursor_on: bool,
+    pub blink_on: bool,
+    pub two_lines: bool,
+    pub eight_bit: bool,
+    /// The high nibble of a 4-bit transfer until the low one is written
+    nibble: Option<u8>,
+}
+
+impl Default for Lcd {
+    fn default() -> Self {
+        // The state after the internal reset of the controller
+        Self {
+            ddram: [b' '; ONE_LINE_LENGTH],
+            address: 0,
+            cgram: false,
+            increment: true,
+            shift_on_write: false,
+            shift: 0,
+            display_on: false,
+            cursor_on: false,
+            blink_on: false,
+            two_lines: false,
+            eight_bit: true,
+            nibble: None,
+        }
+    }
+}
+
+impl Lcd {
+    /// Moves the address counter by one, wrapping around the end of a line (2 lines) or the DDRAM (1 line).
+    fn step_address(&mut self, forward: bool) {
+        if self.two_lines {
+            let (line, offset) = (self.address / SECOND_LINE, self.address % SECOND_LINE);
+            let index = line * LINE_LENGTH + offset;
+            let index = if forward { (index + 1) % (2 * LINE_LENGTH) } else { (index + 2 * LINE_LENGTH - 1) % (2 * LINE_LENGTH) };
+            self.address = index / LINE_LENGTH * SECOND_LINE + index % LINE_LENGTH;
+        } else if forward {
+            self.address = (self.address + 1) % ONE_LINE_LENGTH;
+        } else {
+            self.address = (self.address + ONE_LINE_LENGTH - 1) % ONE_LINE_LENGTH;
+        }
+    }
+
+    fn shift_display(&mut self, left: bool) {
+        let length = if self.two_lines { LINE_LENGTH } else { ONE_LINE_LENGTH };
+        self.shift = if left { (self.shift + 1) % length } else { (self.shift + length - 1) % length };
+    }
+
+    /// Index of a DDRAM address in the state (two-line addresses are folded into 0-79).
+    fn ddram_index(&self, address: usize) -> usize {
+        if self.two_lines {
+            (address / SECOND_LINE) * LINE_LENGTH + address % SECOND_LINE % LINE_LENGTH
+        } else {
+            address % ONE_LINE_LENGTH
+        }
+    }
+
+    /// Runs an instruction (RS low), returning what it did.
+    pub fn instruction(&mut self, value: u8) -> String {
+        match value {
+            0x01 => {
+                self.ddram = [b' '; ONE_LINE_LENGTH];
+                self.address = 0;
+                self.shift = 0;
+                self.cgram = false;
+                self.increment = true;
+                "clear display".to_string()
+            }
+            0x02..=0x03 => {
+                self.address = 0;
+                self.shift = 0;
+                self.cgram = false;
+                "return home".to_string()
+            }
+            0x04..=0x07 => {
+                self.increment = value & 0x2 != 0;
+                self.shift_on_write = value & 0x1 != 0;
+                format!(
+                    "entry mode: {}{}",
+                    if self.increment { "increment" } else { "decrement" },
+                    if self.shift_on_write { ", shift the display" } else { "" }
+                )
+            }
+            0x08..=0x0f => {
+                (self.display_on, self.cursor_on, self.blink_on) = (value & 0x4 != 0, value & 0x2 != 0, value & 0x1 != 0);
+                let on_off = |on: bool| if on { "on" } else { "off" };
+                format!("display {}, cursor {}, blink {}", on_off(self.display_on), on_off(self.cursor_on), on_off(self.blink_on))
+            }
+            0x10..=0x1f => {
+                let right = value & 0x4 != 0;
+                if value & 0x8 != 0 {
+                    self.shift_display(!right);
+                    format!("shift the display {}", if right { "right" } else { "left" })
+                } else {
+                    self.step_address(right);
+                    format!("move the cursor {}", if right { "right" } else { "left" })
+                }
+            }
+            0x20..=0x3f => {
+                self.eight_bit = value & 0x10 != 0;
+                self.two_lines = value & 0x8 != 0;
+                self.nibble = None;
+                format!(
+                    "function set: {}-bit, {} line(s), 5x{} font",
+                    if self.eight_bit { 8 } else { 4 },
+                    if self.two_lines { 2 } else { 1 },
+                    if value & 0x4 != 0 { 10 } else { 8 }
+                )
+            }
+            0x40..=0x7f => {
+                self.cgram = true;
+                self.address = (value & 0x3f) as usize;
+                format!("set CGRAM address {:#04x}", self.address)
+            }
+            _ => {
+                self.cgram = false;
+                self.address = (value & 0x7f) as usize;
+                format!("set DDRAM address {:#04x}", self.address)
+            }
+        }
+    }
+
+    /// Writes a character (RS high) at the address counter, returning what it did.
+    pub fn data(&mut self, value: u8) -> String {
+        if self.cgram {
+            // Custom character patterns are not drawn, only the address is tracked
+            let address = self.address;
+            self.address = if self.increment { (self.address + 1) % 0x40 } else { (self.address + 0x3f) % 0x40 };
+            return format!("CGRAM {:#04x}", address);
+        }
+        let address = self.address;
+        let index = self.ddram_index(address);
+        self.ddram[index] = value;
+        self.step_address(self.increment);
+        if self.shift_on_write {
+            self.shift_display(self.increment);
+        }
+        format!("{} at {:#04x}", character(value), address)
+    }
+
+    /// A byte of the data bus, combining the two nibbles (DB7-DB4) of 4-bit mode. `None` after the first nibble.
+    fn transfer(&mut self, value: u8) -> Option<u8> {
+        if self.eight_bit {
+            return Some(value);
+        }
+        match self.nibble.take() {
+            Some(high) => Some(high << 4 | (value >> 4)),
+            None => {
+                self.nibble = Some(value >> 4);
+                None
+            }
+        }
+    }
+
+    /// The DDRAM address shown in a column of a line.
+    fn shown_address(&self, line: usize, column: usize) -> usize {
+        if self.two_lines {
+            line * SECOND_LINE + (column + self.shift) % LINE_LENGTH
+        } else {
+            (column + self.shift) % ONE_LINE_LENGTH
+        }
+    }
+
+    /// The text shown on the display, a line per display line (blank while the display is off).
+    pub fn lines(&self, columns: usize) -> Vec<String> {
+        let lines = if self.two_lines { 2 } else { 1 };
+        (0..lines)
+            .map(|line| {
+                (0..columns)
+                    .map(|column| match self.display_on {
+                        true => character(self.ddram[self.ddram_index(self.shown_address(line, column))]),
+                        false => ' ',
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// The display in a frame, with the cursor (`^`) under its character when it is on.
+    pub fn render(&self, columns: usize) -> Vec<String> {
+        let border = "─".repeat(columns);
+        let mut rendered = vec![format!("┌{}┐", border)];
+        for (line, text) in self.lines(columns).iter().enumerate() {
+            rendered.push(format!("│{}│", text));
+            let cursor = (0..columns).find(|column| !self.cgram && self.shown_address(line, *column) == self.address);
+            if let Some(column) = cursor.filter(|_| self.display_on && (self.cursor_on || self.blink_on)) {
+                rendered.push(format!(" {}^", " ".repeat(column)));
+            }
+        }
+        rendered.push(format!("└{}┘", border));
+        rendered
+    }
+}
+
+/// A character of the character ROM (A00), custom characters (CGRAM) as `▒`.
+fn character(value: u8) -> char {
+    match value {
+        0x00..=0x0f => '▒',
+        0x5c => '¥',
+        0x7e => '→',
+        0x7f => '←',
+        0x20..=0x7d => value as char,
+        _ => '·',
+    }
+}
+
+/// Runs the writes of a capture on an LCD, returning its final state & what was written.
+/// Without RS the writes are only listed (as `write`), the LCD is left as it was after power on.
+pub fn decode(capture: &Capture, mapping: &LcdMapping) -> Result<(Lcd, Vec<LcdEvent>), String> {
+    let data_port = capture
+        .io
+        .iter()
+        .find(|port| port.io_name == mapping.data || port.io_name.eq_ignore_ascii_case(&mapping.data))
+        .ok_or(format!("\"{}\" (the LCD data bus) is not a port of the capture", mapping.data))?;
+    let data = capture.values(data_port);
+    let enable = decode::signal(capture, &mapping.enable)?;
+    let optional = |name: &Option<String>| name.as_ref().map(|name| decode::signal(capture, name)).transpose();
+    let (rs, rw) = (optional(&mapping.rs)?, optional(&mapping.rw)?);
+
+    let mut lcd = Lcd::default();
+    let mut events = Vec::new();
+    for cycle in 0..enable.len() {
+        // Latched on the falling edge of E, with the bus as it was while E was high
+        let latched = cycle > 0 && enable[cycle - 1] && !enable[cycle];
+        if !latched || rw.as_ref().is_some_and(|rw| rw[cycle - 1]) {
+            continue;
+        }
+        let Some(value) = lcd.transfer(data[cycle - 1] as u8) else {
+            continue;
+        };
+        let (kind, description) = match rs.as_ref().map(|rs| rs[cycle - 1]) {
+            Some(true) => ("data", lcd.data(value)),
+            Some(false) => ("command", lcd.instruction(value)),
+            None => ("write", "RS is not mapped".to_string()),
+        };
+        events.push(LcdEvent { cycle, kind, value, description });
+    }
+    Ok((lcd, events))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ports::{self, frame::Frame};
+
+    #[test]
+    fn test_instructions() {
+        let mut lcd = Lcd::default();
+        assert_eq!(lcd.instruction(0x38), "function set: 8-bit, 2 line(s), 5x8 font");
+        assert_eq!(lcd.instruction(0x0e), "display on, cursor on, blink off");
+        lcd.instruction(0x06);
+        lcd.instruction(0x01);
+        for byte in b"Hi" {
+            lcd.data(*byte);
+        }
+        assert_eq!(lcd.instruction(0xc0), "set DDRAM address 0x40");
+        assert_eq!(lcd.data(b'!'), "! at 0x40");
+        assert_eq!(lcd.lines(4), vec!["Hi  ".to_string(), "!   ".to_string()]);
+        assert_eq!(lcd.render(4), vec!["┌────┐", "│Hi  │", "│!   │", "  ^", "└────┘"]);
+
+        // Writing past the end of the first line continues on the second one
+        lcd.instruction(0x80 | 0x27);
+        lcd.data(b'a');
+        lcd.data(b'b');
+        assert_eq!(lcd.lines(2), vec!["Hi".to_string(), "b ".to_string()]);
+
+        // Shifting the display left shows the characters from the second column
+        lcd.instruction(0x18);
+        assert_eq!(lcd.lines(2), vec!["i ".to_string(), "  ".to_string()]);
+        lcd.instruction(0x08);
+        assert_eq!(lcd.lines(2), vec!["  ".to_string(), "  ".to_string()]);
+    }
+
+    #[test]
+    fn test_decode_capture() {
+        let io = ports::recipe_io("name_display");
+        // lcd_db is on output pins 0-7, lcd_en on 8 & lcd_rst (RS) on 11
+        let mut capture = Capture::new(&io);
+        capture.push(Frame(0), Frame(0));
+        for (byte, rs) in [(0x38u8, 0), (0x0c, 0), (0x01, 0), (b'F', 1), (b'D', 1), (b'E', 1), (0xc0, 0), (b'o', 1), (b'k', 1)] {
+            capture.push(Frame(0), Frame(byte as u64 | 1 << 8 | rs << 11));
+            capture.push(Frame(0), Frame(byte as u64 | rs << 11));
+        }
+
+        // Commands & characters told apart by RS
+        let (lcd, events) = decode(&capture, &LcdMapping::default()).unwrap();
+        let kinds: Vec<&str> = events.iter().map(|event| event.kind).collect();
+        assert_eq!(kinds, vec!["command", "command", "command", "data", "data", "data", "command", "data", "data"]);
+        assert_eq!(lcd.lines(4), vec!["FDE ".to_string(), "ok  ".to_string()]);
+        assert!(lcd.display_on);
+
+        // Without RS the writes are listed but not run
+        let unmapped = LcdMapping { rs: None, ..LcdMapping::default() };
+        let (lcd, events) = decode(&capture, &unmapped).unwrap();
+        assert_eq!(events.len(), 9);
+        assert!(events.iter().all(|event| event.kind == "write"));
+        assert_eq!(events[3].value, b'F');
+        assert!(!lcd.display_on);
+
+        let missing = LcdMapping { enable: "missing".to_string(), ..LcdMapping::default() };
+        assert!(decode(&capture, &missing).is_err());
+
+        // `"rs": null` in the meta file leaves RS unmapped, a missing "rs" is lcd_rst
+        let mapping: LcdMapping = serde_json::from_str(r#"{"rs": null}"#).unwrap();
+        assert_eq!(mapping.rs, None);
+        let mapping: LcdMapping = serde_json::from_str("{}").unwrap();
+        assert_eq!(mapping, LcdMapping::default());
+    }
+}
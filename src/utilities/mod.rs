@@ -1 +1,15 @@
-pub mod fifo;
\ No newline at end of file
+pub mod capture;
+pub mod clock;
+pub mod decode;
+pub mod flash;
+pub mod golden;
+pub mod lcd;
+pub mod monitor;
+pub mod selftest;
+pub mod step;
+pub mod stimulus;
+pub mod testbench;
+pub mod transfer;
+pub mod trigger;
+pub mod vcd;
+pub mod wave;
\ No newline at end of file
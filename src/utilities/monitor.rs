@@ -0,0 +1,164 @@
+/*
+ * Filename: monitor.rs
+ * Desciprtion: Live IO monitor, the current inputs are sent to the board over & over and the table of
+ * the ports is redrawn in place with the ports that changed highlighted & how often they changed
+ */
+
+use std::time::{Duration, Instant};
+
+use owo_colors::OwoColorize;
+use tabled::{Table, Tabled, settings::Style};
+
+use crate::ports::{table, IOPort, IOType};
+
+/// How often the table is redrawn.
+pub const REFRESH: Duration = Duration::from_millis(100);
+
+#[derive(Tabled)]
+struct MonitorRow {
+    port: String,
+    io_type: String,
+    value: String,
+    changes: u64,
+    peripheral: String,
+}
+
+/// The ports being monitored & what was seen of them.
+#[derive(Debug, Clone)]
+pub struct Monitor {
+    /// Indices into the IO ports of the project
+    ports: Vec<usize>,
+    previous: Vec<Option<u64>>,
+    changes: Vec<u64>,
+    /// Changed since the table was last drawn
+    changed: Vec<bool>,
+    pub samples: u64,
+    samples_drawn: u64,
+    drawn: Instant,
+    rate: f64,
+}
+
+impl Monitor {
+    /// Monitors the named ports, or every input & output port when no name is given.
+    pub fn new(io: &[IOPort], names: &[&str]) -> Result<Self, String> {
+        let ports: Vec<usize> = if names.is_empty() {
+            (0..io.len()).filter(|index| !matches!(io[*index].io_type, IOType::DC)).collect()
+        } else {
+            names
+                .iter()
+                .map(|name| {
+                    io.iter()
+                        .position(|port| port.io_name == *name || port.io_name.eq_ignore_ascii_case(name))
+                        .ok_or(format!("\"{}\" is not a port of the project", name))
+                })
+                .collect::<Result<_, _>>()?
+        };
+        if ports.is_empty() {
+            return Err("the project has no ports to monitor".to_string());
+        }
+        let count = ports.len();
+        Ok(Self {
+            ports,
+            previous: vec![None; count],
+            changes: vec![0; count],
+            changed: vec![false; count],
+            samples: 0,
+            samples_drawn: 0,
+            drawn: Instant::now(),
+            rate: 0.0,
+        })
+    }
+
+    /// Counts the ports whose value changed since the last sample (the IO has to be updated with it first).
+    pub fn observe(&mut self, io: &[IOPort]) {
+        for (i, port) in self.ports.iter().enumerate() {
+            let value = io[*port].data;
+            if self.previous[i].is_some_and(|previous| previous != value) {
+                self.changes[i] += 1;
+                self.changed[i] = true;
+            }
+            self.previous[i] = Some(value);
+        }
+        self.samples += 1;
+    }
+
+    /// Whether it is time to redraw the table.
+    pub fn due(&self) -> bool {
+        self.drawn.elapsed() >= REFRESH
+    }
+
+    /// The table of the ports (rows that changed since the last call in yellow) & the sample rate.
+    pub fn render(&mut self, io: &[IOPort]) -> Vec<String> {
+        let elapsed = self.drawn.elapsed().as_secs_f64();
+        if elapsed > 0.0 {
+            self.rate = (self.samples - self.samples_drawn) as f64 / elapsed;
+        }
+        self.samples_drawn = self.samples;
+        self.drawn = Instant::now();
+
+        let rows: Vec<MonitorRow> = self
+            .ports
+            .iter()
+            .zip(self.changes.iter())
+            .map(|(port, changes)| {
+                let port = &io[*port];
+                MonitorRow {
+                    port: port.io_name.clone(),
+                    io_type: port.io_type.to_string(),
+                    value: port.formatted(),
+                    changes: *changes,
+                    peripheral: port.peripheral.clone().unwrap_or_default(),
+                }
+            })
+            .collect();
+        let mut table = Table::new(rows);
+        table::hide_peripherals(&mut table, io);
+        table.with(Style::sharp());
+
+        // Border, header & separator, then a line per port
+        let mut lines: Vec<String> = table.to_string().lines().map(str::to_string).collect();
+        for (i, changed) in self.changed.iter_mut().enumerate() {
+            if std::mem::take(changed) {
+                lines[3 + i] = lines[3 + i].yellow().to_string();
+            }
+        }
+        lines.push(format!("{} samples, {:.0} samples/s", self.samples, self.rate));
+        lines
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ports;
+
+    #[test]
+    fn test_monitor() {
+        let mut io = ports::recipe_io("afifo_test");
+        assert!(Monitor::new(&io, &["missing"]).is_err());
+        let mut monitor = Monitor::new(&io, &["o_rdata", "o_rempty"]).unwrap();
+
+        // o_rdata is on output pins 0-7, o_rempty on 8
+        for bits in [0x100u64, 0x101, 0x101, 0x002] {
+            for port in io.iter_mut() {
+                port.update(bits);
+            }
+            monitor.observe(&io);
+        }
+        assert_eq!(monitor.samples, 4);
+
+        let lines = monitor.render(&io);
+        // Borders, header & 2 ports, then the rate
+        assert_eq!(lines.len(), 7);
+        assert!(lines[3].contains("o_rdata") && lines[3].contains("│ 2 "), "{}", lines[3]);
+        assert!(lines[4].contains("o_rempty") && lines[4].contains("│ 1 "), "{}", lines[4]);
+        assert!(lines[6].starts_with("4 samples"));
+        // Nothing changed since
+        assert!(!monitor.render(&io)[3].contains('\u{1b}'));
+
+        // The peripheral column is only shown once a port is wired to one
+        assert!(!lines[1].contains("peripheral"), "{}", lines[1]);
+        ports::find_port_mut(&mut io, "o_rdata").unwrap().peripheral = Some("leds".to_string());
+        assert!(monitor.render(&io)[1].contains("peripheral"));
+    }
+}
@@ -0,0 +1,202 @@
+/*
+ * Filename: selftest.rs
+ * Desciprtion: Board self-test, a pass/fail report over the whole stack (USB, cfg, engine, programming & IO)
+ */
+
+use std::fmt;
+use tabled::Tabled;
+
+/// Recipe that is programmed by the self-test, it has to echo every input pin on
+/// the output pin with the same index (input i -> output i) within the same cycle.
+/// The HDL is in recipes/loopback, its bitstream has to be built with the FDE toolchain
+/// (the programming & IO steps are skipped until it is).
+pub const LOOPBACK_RECIPE: &str = "loopback";
+
+/// Bits of each tx/rx word that the loopback recipe echoes. fde/VERICOMM_MAP.json has 54 input
+/// pins (tx bits 0-53) and 54 output pins (rx bits 0-53), so the last word only carries 6 bits.
+pub const LOOPBACK_MASK: [u16; 4] = [0xffff, 0xffff, 0xffff, 0x003f];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome { Pass, Fail, Skip }
+impl fmt::Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Outcome::Pass => write!(f, "PASS"),
+            Outcome::Fail => write!(f, "FAIL"),
+            Outcome::Skip => write!(f, "SKIP"),
+        }
+    }
+}
+
+/// A single row of the self-test report.
+#[derive(Tabled)]
+pub struct SelfTestStep {
+    check: String,
+    result: Outcome,
+    detail: String,
+}
+
+#[derive(Default)]
+pub struct SelfTestReport {
+    steps: Vec<SelfTestStep>,
+}
+
+impl SelfTestReport {
+    pub fn new() -> Self {
+        Self { steps: Vec::new() }
+    }
+
+    pub fn record(&mut self, check: &str, result: Outcome, detail: impl Into<String>) {
+        self.steps.push(SelfTestStep { check: check.to_string(), result, detail: detail.into() });
+    }
+
+    /// Records `Pass` or `Fail` depending on the result of a check.
+    pub fn check(&mut self, check: &str, result: Result<String, String>) -> bool {
+        match result {
+            Ok(detail) => { self.record(check, Outcome::Pass, detail); true }
+            Err(detail) => { self.record(check, Outcome::Fail, detail); false }
+        }
+    }
+
+    /// No step failed and at least one passed, skipped steps (e.g. without the loopback bitstream)
+    /// are reported but do not fail the run.
+    pub fn passed(&self) -> bool {
+        self.steps.iter().any(|step| step.result == Outcome::Pass)
+            && self.steps.iter().all(|step| step.result != Outcome::Fail)
+    }
+
+    pub fn skipped(&self) -> usize {
+        self.steps.iter().filter(|step| step.result == Outcome::Skip).count()
+    }
+
+    pub fn steps(&self) -> &Vec<SelfTestStep> {
+        &self.steps
+    }
+}
+
+/// The SMIMS version fields of a healthy board are neither all zero nor all ones
+/// (which is what a failed/garbled cfg read returns).
+pub fn check_versions(version: u16, major: u16, sub: u16, subsub: u16) -> Result<String, String> {
+    let fields = [version, major, sub, subsub];
+    let description = format!("{:#04x} ({}.{}.{})", version, major, sub, subsub);
+
+    if fields.iter().all(|&field| field == 0) {
+        return Err(format!("version fields are all zero {}", description));
+    }
+    if fields.contains(&0xffff) || version == 0xff {
+        return Err(format!("version fields look uninitialized {}", description));
+    }
+    Ok(description)
+}
+
+/// Transfer sizes (in words) for the IO round trips: one frame, then growing by a factor of 4
+/// up to the FIFO size (rounded down to whole frames).
+pub fn buffer_sizes(fifo_size: usize) -> Vec<usize> {
+    let max = fifo_size - fifo_size % 4;
+    let mut sizes = Vec::new();
+    let mut size = 4;
+    while size < max {
+        sizes.push(size);
+        size *= 4;
+    }
+    if max >= 4 {
+        sizes.push(max);
+    }
+    sizes
+}
+
+/// A deterministic pattern (walking ones mixed with a counter) that toggles every echoed bit.
+pub fn loopback_pattern(words: usize) -> Vec<u16> {
+    (0..words)
+        .map(|i| {
+            let walking = 1u16 << (i % 16);
+            let counter = (i / 16) as u16;
+            (walking ^ counter.rotate_left(8)) & LOOPBACK_MASK[i % 4]
+        })
+        .collect()
+}
+
+/// Compares what was sent with what the loopback recipe returned.
+pub fn compare_loopback(tx: &[u16], rx: &[u16]) -> Result<String, String> {
+    if rx.len() != tx.len() {
+        return Err(format!("sent {} words, received {}", tx.len(), rx.len()));
+    }
+    let mismatches: Vec<usize> = tx
+        .iter()
+        .zip(rx.iter())
+        .enumerate()
+        .filter(|(i, (t, r))| (*t ^ *r) & LOOPBACK_MASK[i % 4] != 0)
+        .map(|(i, _)| i)
+        .collect();
+
+    match mismatches.first() {
+        None => Ok(format!("{} words ({} frames) echoed", tx.len(), tx.len() / 4)),
+        Some(&first) => Err(format!(
+            "{} of {} words differ, first at word {}: sent {:#06x}, received {:#06x}",
+            mismatches.len(), tx.len(), first, tx[first], rx[first]
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_versions() {
+        assert!(check_versions(0x02, 2, 1, 0).is_ok());
+        assert!(check_versions(0, 0, 0, 0).is_err());
+        assert!(check_versions(0xffff, 0xffff, 0xffff, 0xffff).is_err());
+    }
+
+    #[test]
+    fn test_report_passed() {
+        let mut report = SelfTestReport::new();
+        assert!(!report.passed());
+        report.check("Device opens", Ok("USB handle opened".to_string()));
+        assert!(report.passed());
+        report.record("Program loopback", Outcome::Skip, "recipe \"loopback\" not found");
+        assert!(report.passed());
+        assert_eq!(report.skipped(), 1);
+        report.check("Engine reset", Err("timeout".to_string()));
+        assert!(!report.passed());
+
+        // Nothing but skipped steps is not a pass
+        let mut report = SelfTestReport::new();
+        report.record("Program loopback", Outcome::Skip, "recipe \"loopback\" not found");
+        assert!(!report.passed());
+    }
+
+    #[test]
+    fn test_loopback_mask() {
+        let map: serde_json::Value = serde_json::from_str(crate::board::DEFAULT_PIN_MAP).unwrap();
+        let echoed: u32 = LOOPBACK_MASK.iter().map(|word| word.count_ones()).sum();
+        assert_eq!(echoed as usize, map["input"].as_object().unwrap().len());
+        assert_eq!(echoed as usize, map["output"].as_object().unwrap().len());
+    }
+
+    #[test]
+    fn test_buffer_sizes() {
+        assert_eq!(buffer_sizes(0), Vec::<usize>::new());
+        assert_eq!(buffer_sizes(4), vec![4]);
+        assert_eq!(buffer_sizes(64), vec![4, 16, 64]);
+        assert_eq!(buffer_sizes(0x3ff), vec![4, 16, 64, 256, 1020]);
+    }
+
+    #[test]
+    fn test_loopback_compare() {
+        let tx = loopback_pattern(64);
+        assert!(compare_loopback(&tx, &tx).is_ok());
+
+        // Bits outside of the mask are not echoed and are ignored
+        let mut rx = tx.clone();
+        rx[3] |= 0xffc0;
+        assert!(compare_loopback(&tx, &rx).is_ok());
+
+        rx[5] ^= 0x1;
+        let err = compare_loopback(&tx, &rx).unwrap_err();
+        assert!(err.contains("first at word 5"), "{}", err);
+
+        assert!(compare_loopback(&tx, &tx[..60]).is_err());
+    }
+}
@@ -0,0 +1,22 @@
+/*
+ * Filename: step.rs
+ * Desciprtion: Single-step (gated clock) mode, the design clock only advances one cycle
+ * per frame sent through VeriComm so the design can be stepped cycle by cycle (if the engine runs in it)
+ */
+
+use crate::helper::smims_cfg::CfgField;
+use crate::ports::{frame::Frame, IOPort, IOType};
+use crate::vlfd::cfg::CfgInfo;
+
+/// Whether the engine gates the design clock (single-step mode), the clock then only advances one cycle
+/// per frame. The mode is read from the configuration space, the vlfd driver cannot change it.
+pub fn is_single_step(cfg: &impl CfgInfo) -> bool {
+    CfgField::IsVericommClockcontinue.read(cfg) == 0
+}
+
+/// Updates the output ports with a sampled frame, inputs keep the values they are driven with.
+pub fn update_outputs(io: &mut [IOPort], frame: Frame) {
+    for port in io.iter_mut().filter(|port| matches!(port.io_type, IOType::OUTPUT)) {
+        port.update(frame.bits());
+    }
+}
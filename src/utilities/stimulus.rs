@@ -0,0 +1,363 @@
+/*
+ * Filename: stimulus.rs
+ * Desciprtion: Driving the inputs of the loaded project from a stimulus (a VCD file from simulation),
+ * signals are bound to input ports by name and their value changes turned into per-cycle frames
+ */
+
+use crate::ports::{frame::Frame, IOPort, IOType};
+use super::golden::Expected;
+use super::vcd::VcdFile;
+
+/// A VCD signal driving (a bit of) an input port.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Binding {
+    /// Index into `VcdFile::signals`
+    pub signal: usize,
+    /// Index into the IO ports of the project
+    pub port: usize,
+    /// The declared index of the single bit of the port that is driven (`i_wdata[3]`), the whole port when `None`
+    pub bit: Option<usize>,
+    /// Where that bit is in the value of the port, from the declared range of the port
+    pub position: Option<usize>,
+}
+
+impl Binding {
+    /// Binds a signal to a port, or to the bit of it with the declared index `bit`.
+    fn new(signal: usize, port: usize, bit: Option<usize>, io: &[IOPort]) -> Result<Self, String> {
+        let position = match bit {
+            Some(bit) => {
+                let range = io[port].range();
+                let position = range
+                    .position(bit)
+                    .ok_or(format!("{}[{}] is outside of the range {} of the port", io[port].io_name, bit, range))?;
+                Some(position)
+            }
+            None => None,
+        };
+        Ok(Self { signal, port, bit, position })
+    }
+}
+
+/// Splits `name[3]` into (`name`, `Some(3)`).
+fn split_bit(name: &str) -> (&str, Option<usize>) {
+    let bit = name.find('[').and_then(|split| {
+        let bit = name[split + 1..].strip_suffix(']')?.trim().parse().ok()?;
+        Some((split, bit))
+    });
+    match bit {
+        Some((split, bit)) => (&name[..split], Some(bit)),
+        None => (name, None),
+    }
+}
+
+fn find_port(io: &[IOPort], name: &str, is_type: impl Fn(&IOType) -> bool) -> Option<usize> {
+    io.iter()
+        .position(|port| is_type(&port.io_type) && port.io_name == name)
+        .or_else(|| io.iter().position(|port| is_type(&port.io_type) && port.io_name.eq_ignore_ascii_case(name)))
+}
+
+fn find_input(io: &[IOPort], name: &str) -> Option<usize> {
+    find_port(io, name, |io_type| matches!(io_type, IOType::INPUT))
+}
+
+/// Parses a `--map sig=port` argument.
+pub fn parse_map(map: &str) -> Result<(String, String), String> {
+    match map.split_once('=') {
+        Some((signal, port)) if !signal.is_empty() && !port.is_empty() => Ok((signal.to_string(), port.to_string())),
+        _ => Err(format!("invalid mapping \"{}\", expected sig=port", map)),
+    }
+}
+
+/// Binds the VCD signals to input ports, explicit `maps` (signal -> port) first, then by matching names.
+/// Signals that do not match any input are left out.
+pub fn bind(vcd: &VcdFile, io: &[IOPort], maps: &[(String, String)]) -> Result<Vec<Binding>, String> {
+    let mut bindings = Vec::new();
+
+    for (signal, port) in maps {
+        let index = vcd
+            .signals
+            .iter()
+            .position(|s| &s.name == signal || &s.reference == signal)
+            .ok_or(format!("signal \"{}\" is not in the VCD file", signal))?;
+        let (port_name, bit) = split_bit(port);
+        let port_index = find_input(io, port_name).ok_or(format!("\"{}\" is not an input port", port_name))?;
+        bindings.push(Binding::new(index, port_index, bit, io)?);
+    }
+
+    for (index, signal) in vcd.signals.iter().enumerate() {
+        if bindings.iter().any(|binding| binding.signal == index) {
+            continue;
+        }
+        if let Some(port) = find_input(io, &signal.reference) {
+            bindings.push(Binding::new(index, port, signal.bit, io)?);
+        }
+    }
+    Ok(bindings)
+}
+
+/// Binds the VCD signals to output ports by name, their values are what the board is expected to return.
+pub fn bind_outputs(vcd: &VcdFile, io: &[IOPort]) -> Result<Vec<Binding>, String> {
+    vcd.signals
+        .iter()
+        .enumerate()
+        .filter_map(|(index, signal)| {
+            let port = find_port(io, &signal.reference, |io_type| matches!(io_type, IOType::OUTPUT))?;
+            Some(Binding::new(index, port, signal.bit, io))
+        })
+        .collect()
+}
+
+/// The values (and unknown bits) of every signal at a point of the dump.
+#[derive(Clone)]
+struct Snapshot {
+    values: Vec<u64>,
+    unknown: Vec<u64>,
+}
+
+/// Snapshots of the signals at every cycle, and after the last change. Without a clock every timestamp
+/// is a cycle (sampled after its changes), with a clock a cycle is sampled right before each rising edge of it.
+fn sample_points(vcd: &VcdFile, clock: Option<usize>) -> (Vec<Snapshot>, Snapshot) {
+    let mut current = Snapshot { values: vec![0u64; vcd.signals.len()], unknown: vec![0u64; vcd.signals.len()] };
+    let mut samples = Vec::new();
+
+    let mut changes = vcd.changes.iter().zip(vcd.unknown.iter()).peekable();
+    while let Some(&(&(time, _, _), _)) = changes.peek() {
+        // All the changes of one timestamp
+        let mut group = Vec::new();
+        while let Some(&(&change, &unknown)) = changes.peek() {
+            if change.0 != time {
+                break;
+            }
+            group.push((change, unknown));
+            changes.next();
+        }
+
+        let rising = clock.is_some_and(|clock| {
+            group.iter().any(|&((_, signal, value), _)| signal == clock && value == 1 && current.values[clock] == 0)
+        });
+        if rising {
+            samples.push(current.clone());
+        }
+        for ((_, signal, value), unknown) in group {
+            current.values[signal] = value;
+            current.unknown[signal] = unknown;
+        }
+        if clock.is_none() {
+            samples.push(current.clone());
+        }
+    }
+    (samples, current)
+}
+
+/// Applies the bound signals to the values of their ports (starting from `current`), bit selects only change their bit
+/// (at its position in the value, see `Binding::position`).
+/// Returns (port, value, mask of the bits that are known).
+fn port_values(snapshot: &Snapshot, bindings: &[Binding], current: impl Fn(usize) -> u64) -> Vec<(usize, u64, u64)> {
+    let mut ports: Vec<(usize, u64, u64)> = Vec::new();
+    for binding in bindings {
+        let value = snapshot.values[binding.signal];
+        let known = !snapshot.unknown[binding.signal];
+        let index = match ports.iter().position(|(port, _, _)| *port == binding.port) {
+            Some(index) => index,
+            None => {
+                ports.push((binding.port, current(binding.port), 0));
+                ports.len() - 1
+            }
+        };
+        let (_, current, mask) = &mut ports[index];
+        match binding.position {
+            Some(bit) if bit < 64 => {
+                *current = (*current & !(1 << bit)) | ((value & 0x1) << bit);
+                *mask = (*mask & !(1 << bit)) | ((known & 0x1) << bit);
+            }
+            Some(_) => {}
+            None => {
+                *current = value;
+                *mask = known;
+            }
+        }
+    }
+    ports
+}
+
+/// Converts the value changes into one input frame per cycle. Without a clock every timestamp is
+/// a cycle (sampled after its changes), with a clock a cycle is sampled right before each rising edge
+/// of it (what the design registers on that edge). Ports that are not driven keep their current value.
+pub fn frames(vcd: &VcdFile, io: &[IOPort], bindings: &[Binding], clock: Option<usize>) -> Vec<Frame> {
+    let mut io = io.to_vec();
+    let (samples, _) = sample_points(vcd, clock);
+    samples
+        .iter()
+        .map(|snapshot| {
+            for (port, value, _) in port_values(snapshot, bindings, |port| io[port].data) {
+                io[port].change_value(value);
+            }
+            Frame::from_io(&io)
+        })
+        .collect()
+}
+
+/// The expected values of the output ports bound with `bind_outputs`, one per port & cycle, unknown (`x`/`z`)
+/// and unbound bits are don't care. With a clock, the outputs of a cycle are the ones right before the
+/// next rising edge (what the design settled to after registering the inputs of the cycle).
+pub fn expectations(vcd: &VcdFile, bindings: &[Binding], clock: Option<usize>) -> Vec<Expected> {
+    let (mut samples, last) = sample_points(vcd, clock);
+    if clock.is_some() && !samples.is_empty() {
+        samples.remove(0);
+        samples.push(last);
+    }
+    samples
+        .iter()
+        .enumerate()
+        .flat_map(|(cycle, snapshot)| {
+            port_values(snapshot, bindings, |_| 0)
+                .into_iter()
+                .filter(|&(_, _, mask)| mask != 0)
+                .map(move |(port, value, mask)| Expected { cycle, port, value, mask })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ports;
+    use crate::utilities::vcd::parse_vcd;
+
+    const STIMULUS: &str = "
+        $scope module tb $end
+        $var reg 1 ! clk $end
+        $var reg 8 \" i_wdata [7:0] $end
+        $var reg 1 # nReset $end
+        $var reg 1 $ write_enable $end
+        $var reg 1 % o_rdata [0] $end
+        $upscope $end
+        $enddefinitions $end
+        #0 0! b0 \" 0# 0$
+        #5 1!
+        #10 0! b11 \" 1# 1$
+        #15 1!
+        #20 0! 0$
+        #25 1!
+    ";
+
+    #[test]
+    fn test_bind() {
+        let io = ports::recipe_io("afifo_test");
+        let vcd = parse_vcd(STIMULUS).unwrap();
+
+        // By name: i_wdata & nReset, o_rdata is an output and the clock is not a pin
+        let bindings = bind(&vcd, &io, &[]).unwrap();
+        assert_eq!(bindings.len(), 2);
+
+        let maps = vec![parse_map("write_enable=i_wr").unwrap(), parse_map("tb.clk=i_rd").unwrap()];
+        let bindings = bind(&vcd, &io, &maps).unwrap();
+        assert_eq!(bindings.len(), 4);
+
+        assert!(bind(&vcd, &io, &[parse_map("missing=i_wr").unwrap()]).is_err());
+        assert!(bind(&vcd, &io, &[parse_map("clk=o_rdata").unwrap()]).is_err());
+        assert!(parse_map("clk").is_err());
+
+        // o_rdata [0] is a bit of an output
+        let outputs = bind_outputs(&vcd, &io).unwrap();
+        assert_eq!(outputs.len(), 1);
+        assert_eq!(outputs[0].bit, Some(0));
+    }
+
+    #[test]
+    fn test_frames() {
+        let io = ports::recipe_io("afifo_test");
+        let vcd = parse_vcd(STIMULUS).unwrap();
+        let bindings = bind(&vcd, &io, &[parse_map("write_enable=i_wr").unwrap()]).unwrap();
+
+        // Sampled right before each rising edge of clk
+        let clocked = frames(&vcd, &io, &bindings, Some(0));
+        assert_eq!(clocked, vec![Frame(0x0), Frame(0xc03), Frame(0x403)]);
+
+        // Every timestamp is a cycle
+        let unclocked = frames(&vcd, &io, &bindings, None);
+        assert_eq!(unclocked.len(), 6);
+        assert_eq!(unclocked[2], Frame(0xc03));
+    }
+
+    #[test]
+    fn test_bit_select() {
+        let io = ports::recipe_io("afifo_test");
+        let vcd = parse_vcd("
+            $var reg 1 ! d0 $end
+            $var reg 1 \" d1 $end
+            $enddefinitions $end
+            #0 1! 0\"
+            #1 1\"
+        ").unwrap();
+        let maps = vec![parse_map("d0=i_wdata[0]").unwrap(), parse_map("d1=i_wdata[7]").unwrap()];
+        let bindings = bind(&vcd, &io, &maps).unwrap();
+        assert_eq!(frames(&vcd, &io, &bindings, None), vec![Frame(0x01), Frame(0x81)]);
+    }
+
+    #[test]
+    fn test_bit_select_declared_range() {
+        let vcd = parse_vcd("
+            $var reg 1 ! d0 $end
+            $var reg 1 \" d1 $end
+            $enddefinitions $end
+            #0 1! 0\"
+            #1 1\"
+        ").unwrap();
+        let maps = vec![parse_map("d0=i_wdata[0]").unwrap(), parse_map("d1=i_wdata[7]").unwrap()];
+
+        // Descending: index 0 is the MSB of [0:7], the pins of i_wdata are still driven by declared index
+        let mut io = ports::recipe_io("afifo_test");
+        ports::find_port_mut(&mut io, "i_wdata").unwrap().declare(ports::bus::Range { msb: 0, lsb: 7 }).unwrap();
+        let bindings = bind(&vcd, &io, &maps).unwrap();
+        assert_eq!((bindings[0].position, bindings[1].position), (Some(7), Some(0)));
+        assert_eq!(frames(&vcd, &io, &bindings, None), vec![Frame(0x01), Frame(0x81)]);
+
+        // Offset: the pins of i_wdata constrained as i_wdata[8:1], there is no index 0
+        let port_mappings = ports::fde_parse_ports().unwrap();
+        let constraints: Vec<ports::Port> = ports::recipe_constraints("afifo_test")
+            .into_iter()
+            .map(|mut constraint| {
+                let index = ports::bus::parse_port_name(&constraint.name).index;
+                if let (true, Some(index)) = (constraint.name.starts_with("i_wdata"), index) {
+                    constraint.name = format!("i_wdata[{}]", index + 1);
+                }
+                ports::new_port(constraint, port_mappings.clone())
+            })
+            .collect();
+        let io = ports::group_ports(&constraints, port_mappings);
+        assert!(bind(&vcd, &io, &maps).unwrap_err().contains("outside of the range [8:1]"));
+        let maps = vec![parse_map("d0=i_wdata[1]").unwrap(), parse_map("d1=i_wdata[8]").unwrap()];
+        let bindings = bind(&vcd, &io, &maps).unwrap();
+        assert_eq!(frames(&vcd, &io, &bindings, None), vec![Frame(0x01), Frame(0x81)]);
+    }
+
+    #[test]
+    fn test_expectations() {
+        let io = ports::recipe_io("afifo_test");
+        let vcd = parse_vcd("
+            $var reg 1 ! clk $end
+            $var wire 8 \" o_rdata [7:0] $end
+            $var wire 1 # o_rempty $end
+            $enddefinitions $end
+            #0 0! bx \" 1#
+            #5 1!
+            #6 b1 \" 0#
+            #10 0!
+            #15 1!
+            #16 b1x \"
+        ").unwrap();
+        let bindings = bind_outputs(&vcd, &io).unwrap();
+        let o_rdata = io.iter().position(|port| port.io_name == "o_rdata").unwrap();
+
+        // Outputs of a cycle settle before the next rising edge, the unknown bits are don't care
+        let expected = expectations(&vcd, &bindings, Some(0));
+        let rdata: Vec<(usize, u64, u64)> = expected
+            .iter()
+            .filter(|expected| expected.port == o_rdata)
+            .map(|expected| (expected.cycle, expected.value, expected.mask))
+            .collect();
+        assert_eq!(rdata, vec![(0, 0x1, !0), (1, 0x2, !0x1)]);
+        assert_eq!(expected.len(), 4);
+    }
+}
@@ -0,0 +1,547 @@
+/*
+ * Filename: testbench.rs
+ * Desciprtion: A small testbench language using the port names of the project, compiled into
+ * input frames & expected output values:
+ *
+ *     # comments start with `#` or `//`, statements end at a newline or `;`
+ *     set nReset=1 rst=0        // assignments (`set` is optional)
+ *     tick                      // one clock cycle with the current inputs, `tick 3` for three
+ *     wait 2                    // hold the inputs for two cycles
+ *     repeat 3 { i_wr=1 i_wdata=$i+1; tick }   // `$i` counts the iterations from 0
+ *     expect o_rdata==3         // checked against the outputs of the last cycle
+ *     expect o_rdata==0b1x1x    // `x` digits are not checked
+ */
+
+use std::collections::BTreeMap;
+use std::ops::RangeInclusive;
+
+use tabled::Tabled;
+
+use crate::ports::{self, frame::Frame, IOPort, IOType};
+use super::golden::{self, Expected};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Word(String),
+    Assign,
+    Equals,
+    Open,
+    Close,
+    End,
+}
+
+/// A value in a statement: a literal or the iteration counter of the innermost `repeat` plus an offset.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Value {
+    Literal(u64),
+    Iteration(i64),
+}
+
+impl Value {
+    fn parse(word: &str) -> Result<Self, String> {
+        match word.strip_prefix("$i") {
+            Some("") => Ok(Value::Iteration(0)),
+            Some(offset) => {
+                let (sign, number) = match offset.split_at(1) {
+                    ("+", number) => (1, number),
+                    ("-", number) => (-1, number),
+                    _ => return Err(format!("invalid value \"{}\"", word)),
+                };
+                let number = ports::parse_port_value(number)?;
+                Ok(Value::Iteration(sign * number as i64))
+            }
+            None => Ok(Value::Literal(ports::parse_port_value(word)?)),
+        }
+    }
+
+    fn resolve(&self, iteration: Option<u64>) -> Result<u64, String> {
+        match *self {
+            Value::Literal(value) => Ok(value),
+            Value::Iteration(offset) => {
+                let iteration = iteration.ok_or("$i is only defined inside of repeat")?;
+                u64::try_from(iteration as i64 + offset).map_err(|_| format!("$i{:+} is negative", offset))
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Statement {
+    Assign { line: usize, assignments: Vec<(String, Value)> },
+    Tick { line: usize, cycles: u64 },
+    Repeat { line: usize, count: u64, body: Vec<Statement> },
+    /// Only the bits set in `mask` are checked
+    Expect { line: usize, port: String, value: Value, mask: u64 },
+}
+
+fn tokenize(text: &str) -> Vec<(usize, Token)> {
+    let mut tokens = Vec::new();
+    for (number, line) in text.lines().enumerate() {
+        let line = line.split('#').next().unwrap_or("");
+        let line = line.split("//").next().unwrap_or("");
+
+        let mut word = String::new();
+        let mut chars = line.chars().peekable();
+        while let Some(c) = chars.next() {
+            let token = match c {
+                '{' => Some(Token::Open),
+                '}' => Some(Token::Close),
+                ';' => Some(Token::End),
+                '=' if chars.peek() == Some(&'=') => {
+                    chars.next();
+                    Some(Token::Equals)
+                }
+                '=' => Some(Token::Assign),
+                c if c.is_whitespace() => None,
+                c => {
+                    word.push(c);
+                    continue;
+                }
+            };
+            if !word.is_empty() {
+                tokens.push((number + 1, Token::Word(std::mem::take(&mut word))));
+            }
+            if let Some(token) = token {
+                tokens.push((number + 1, token));
+            }
+        }
+        if !word.is_empty() {
+            tokens.push((number + 1, Token::Word(word)));
+        }
+        tokens.push((number + 1, Token::End));
+    }
+    tokens
+}
+
+struct Parser {
+    tokens: Vec<(usize, Token)>,
+    position: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position).map(|(_, token)| token)
+    }
+
+    fn line(&self) -> usize {
+        self.tokens
+            .get(self.position)
+            .or(self.tokens.last())
+            .map_or(0, |(line, _)| *line)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.position).map(|(_, token)| token.clone());
+        self.position += 1;
+        token
+    }
+
+    fn word(&mut self, what: &str) -> Result<String, String> {
+        match self.next() {
+            Some(Token::Word(word)) => Ok(word),
+            _ => Err(format!("expected {}", what)),
+        }
+    }
+
+    fn number(&mut self, what: &str) -> Result<u64, String> {
+        let word = self.word(what)?;
+        ports::parse_port_value(&word)
+    }
+
+    fn assignments(&mut self) -> Result<Vec<(String, Value)>, String> {
+        let mut assignments = Vec::new();
+        while let Some(Token::Word(_)) = self.peek() {
+            let port = self.word("a port name")?;
+            if self.next() != Some(Token::Assign) {
+                return Err(format!("expected `=` after {}", port));
+            }
+            let value = Value::parse(&self.word("a value")?)?;
+            assignments.push((port, value));
+        }
+        if assignments.is_empty() {
+            return Err("expected port=value".to_string());
+        }
+        Ok(assignments)
+    }
+
+    /// Statements up to the end of the input or a closing `}`.
+    fn block(&mut self, nested: bool) -> Result<Vec<Statement>, String> {
+        let mut statements = Vec::new();
+        loop {
+            let line = self.line();
+            let keyword = match self.peek() {
+                None if nested => return Err("missing `}`".to_string()),
+                None => return Ok(statements),
+                Some(Token::Close) if nested => {
+                    self.next();
+                    return Ok(statements);
+                }
+                Some(Token::End) => {
+                    self.next();
+                    continue;
+                }
+                Some(Token::Word(word)) => word.clone(),
+                Some(_) => return Err(format!("line {}: unexpected `{}`", line, self.describe())),
+            };
+
+            let statement = match keyword.as_str() {
+                "set" => {
+                    self.next();
+                    self.assignments().map(|assignments| Statement::Assign { line, assignments })
+                }
+                "tick" | "wait" => {
+                    self.next();
+                    match self.peek() {
+                        Some(Token::Word(_)) => self.number("a number of cycles"),
+                        _ if keyword == "wait" => Err("expected a number of cycles".to_string()),
+                        _ => Ok(1),
+                    }
+                    .map(|cycles| Statement::Tick { line, cycles })
+                }
+                "repeat" => {
+                    self.next();
+                    let count = self.number("a repeat count");
+                    while self.peek() == Some(&Token::End) {
+                        self.next();
+                    }
+                    match (count, self.next()) {
+                        (Ok(count), Some(Token::Open)) => {
+                            self.block(true).map(|body| Statement::Repeat { line, count, body })
+                        }
+                        (Ok(_), _) => Err("expected `{`".to_string()),
+                        (Err(e), _) => Err(e),
+                    }
+                }
+                "expect" => {
+                    self.next();
+                    let port = self.word("a port name");
+                    match (port, self.next()) {
+                        (Ok(port), Some(Token::Equals)) => self.word("a value").and_then(|value| {
+                            let (value, mask) = if value.starts_with("$i") {
+                                (Value::parse(&value)?, u64::MAX)
+                            } else {
+                                let (value, mask) = golden::parse_expected(&value)?;
+                                (Value::Literal(value), mask)
+                            };
+                            Ok(Statement::Expect { line, port, value, mask })
+                        }),
+                        (Ok(_), _) => Err("expected `==`".to_string()),
+                        (Err(e), _) => Err(e),
+                    }
+                }
+                _ => self.assignments().map(|assignments| Statement::Assign { line, assignments }),
+            };
+            let statement = statement.map_err(|e| if e.starts_with("line ") { e } else { format!("line {}: {}", line, e) })?;
+            statements.push(statement);
+
+            match self.peek() {
+                None | Some(Token::End) | Some(Token::Close) => {}
+                Some(_) => return Err(format!("line {}: unexpected `{}`", self.line(), self.describe())),
+            }
+        }
+    }
+
+    fn describe(&self) -> String {
+        match self.peek() {
+            Some(Token::Word(word)) => word.clone(),
+            Some(Token::Assign) => "=".to_string(),
+            Some(Token::Equals) => "==".to_string(),
+            Some(Token::Open) => "{".to_string(),
+            Some(Token::Close) => "}".to_string(),
+            Some(Token::End) | None => "end of line".to_string(),
+        }
+    }
+}
+
+/// Parses a testbench, errors carry the line they occurred on.
+pub fn parse(text: &str) -> Result<Vec<Statement>, String> {
+    let mut parser = Parser { tokens: tokenize(text), position: 0 };
+    parser.block(false)
+}
+
+/// An output value to check after a cycle, and the line of the `expect`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Expect {
+    pub line: usize,
+    pub expected: Expected,
+}
+
+/// A compiled testbench: the input frame of every cycle and the checks on the sampled outputs.
+#[derive(Debug, Clone, Default)]
+pub struct Program {
+    pub frames: Vec<Frame>,
+    pub expects: Vec<Expect>,
+    /// Cycles each source line applied to (assignments take effect from the cycle after them)
+    pub lines: BTreeMap<usize, RangeInclusive<usize>>,
+}
+
+impl Program {
+    fn touch(&mut self, line: usize, first: usize, last: usize) {
+        let range = self.lines.entry(line).or_insert(first..=last);
+        *range = (*range.start()).min(first)..=(*range.end()).max(last);
+    }
+}
+
+fn find_port(io: &[IOPort], name: &str, io_type: IOType) -> Result<usize, String> {
+    let matches = |port: &IOPort| std::mem::discriminant(&port.io_type) == std::mem::discriminant(&io_type);
+    io.iter()
+        .position(|port| matches(port) && port.io_name == name)
+        .or_else(|| io.iter().position(|port| matches(port) && port.io_name.eq_ignore_ascii_case(name)))
+        .ok_or(format!("\"{}\" is not an {} port", name, io_type.to_string().to_lowercase()))
+}
+
+fn compile_block(
+    statements: &[Statement],
+    io: &mut Vec<IOPort>,
+    iteration: Option<u64>,
+    program: &mut Program,
+) -> Result<(), String> {
+    for statement in statements {
+        match statement {
+            Statement::Assign { line, assignments } => {
+                for (name, value) in assignments {
+                    let port = find_port(io, name, IOType::INPUT).map_err(|e| format!("line {}: {}", line, e))?;
+                    value
+                        .resolve(iteration)
+                        .and_then(|value| io[port].set_value(value))
+                        .map_err(|e| format!("line {}: {}", line, e))?;
+                }
+                let next = program.frames.len();
+                program.touch(*line, next, next);
+            }
+            Statement::Tick { line, cycles } => {
+                let first = program.frames.len();
+                let frame = Frame::from_io(io);
+                program.frames.extend(std::iter::repeat_n(frame, *cycles as usize));
+                program.touch(*line, first, program.frames.len().saturating_sub(1).max(first));
+            }
+            Statement::Repeat { count, body, .. } => {
+                for i in 0..*count {
+                    compile_block(body, io, Some(i), program)?;
+                }
+            }
+            Statement::Expect { line, port, value, mask } => {
+                let cycle = program
+                    .frames
+                    .len()
+                    .checked_sub(1)
+                    .ok_or(format!("line {}: expect before the first tick", line))?;
+                let port = find_port(io, port, IOType::OUTPUT).map_err(|e| format!("line {}: {}", line, e))?;
+                let value = value.resolve(iteration).map_err(|e| format!("line {}: {}", line, e))?;
+                program.expects.push(Expect { line: *line, expected: Expected { cycle, port, value, mask: *mask } });
+                program.touch(*line, cycle, cycle);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Compiles a testbench against the IO ports of the loaded project, inputs start from their current values.
+pub fn compile(statements: &[Statement], io: &[IOPort]) -> Result<Program, String> {
+    let mut program = Program::default();
+    compile_block(statements, &mut io.to_vec(), None, &mut program)?;
+    Ok(program)
+}
+
+/// The outcome of an `expect`, `actual` is `None` when the cycle was not sampled.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExpectResult {
+    pub expect: Expect,
+    pub actual: Option<u64>,
+}
+
+impl ExpectResult {
+    pub fn passed(&self) -> bool {
+        self.actual.is_some_and(|actual| self.expect.expected.matches(actual))
+    }
+}
+
+/// Compares the expected values with the sampled output frames.
+pub fn check(program: &Program, io: &[IOPort], outputs: &[Frame]) -> Vec<ExpectResult> {
+    program
+        .expects
+        .iter()
+        .map(|expect| {
+            let actual = outputs
+                .get(expect.expected.cycle)
+                .map(|&frame| golden::sampled_value(&io[expect.expected.port], frame));
+            ExpectResult { expect: expect.clone(), actual }
+        })
+        .collect()
+}
+
+/// Every failed `expect` of a run, in the order of the cycles.
+pub fn mismatches(program: &Program, io: &[IOPort], outputs: &[Frame]) -> Vec<golden::Mismatch> {
+    let mut expected: Vec<Expected> = program.expects.iter().map(|expect| expect.expected).collect();
+    expected.sort_by_key(|expected| expected.cycle);
+    golden::compare(io, &expected, outputs)
+}
+
+/// A row of the report of a run, one per source line.
+#[derive(Tabled)]
+pub struct StatementResult {
+    line: usize,
+    statement: String,
+    cycles: String,
+    result: String,
+}
+
+/// Per statement (source line) results of a run.
+pub fn report(text: &str, program: &Program, results: &[ExpectResult], io: &[IOPort]) -> Vec<StatementResult> {
+    let source: Vec<&str> = text.lines().collect();
+    program
+        .lines
+        .iter()
+        .map(|(&line, cycles)| {
+            let checks: Vec<&ExpectResult> = results.iter().filter(|result| result.expect.line == line).collect();
+            let failed: Vec<&&ExpectResult> = checks.iter().filter(|result| !result.passed()).collect();
+            let result = match (checks.len(), failed.first()) {
+                (0, _) => "ok".to_string(),
+                (1, None) => "PASS".to_string(),
+                (n, None) => format!("PASS ({}/{})", n, n),
+                (n, Some(first)) => {
+                    let expected = &first.expect.expected;
+                    let port = &io[expected.port];
+                    format!(
+                        "FAIL ({}/{}), cycle {}: {} expected {}, got {}",
+                        n - failed.len(),
+                        n,
+                        expected.cycle,
+                        port.io_name,
+                        golden::format_expected(port, expected.value, expected.mask),
+                        first.actual.map_or("nothing".to_string(), |actual| port.format.render(actual, port.width()))
+                    )
+                }
+            };
+            let cycles = if cycles.start() == cycles.end() {
+                cycles.start().to_string()
+            } else {
+                format!("{}-{}", cycles.start(), cycles.end())
+            };
+            StatementResult {
+                line,
+                statement: source.get(line - 1).map_or(String::new(), |text| text.trim().to_string()),
+                cycles,
+                result,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The sequence of the `test` command (reset, write 1, 2, 3 then read them back)
+    const AFIFO_TEST: &str = "
+        # Reset
+        set rst=1 nReset=1; tick
+        rst=0 nReset=0; tick
+        rst=1; tick
+        rst=0 nReset=1; tick
+
+        // Write 1, 2, 3
+        repeat 3 { i_wr=1 i_wdata=$i+1; tick }
+        i_wr=0
+        wait 2
+
+        repeat 3 {
+            i_rd=1; tick
+            expect o_rdata==$i+1
+        }
+    ";
+
+    #[test]
+    fn test_parse() {
+        let statements = parse("set a=1 b=0x2; tick 3\nrepeat 2 { a=$i }\nexpect c==0b1").unwrap();
+        assert_eq!(
+            statements,
+            vec![
+                Statement::Assign { line: 1, assignments: vec![("a".to_string(), Value::Literal(1)), ("b".to_string(), Value::Literal(2))] },
+                Statement::Tick { line: 1, cycles: 3 },
+                Statement::Repeat {
+                    line: 2,
+                    count: 2,
+                    body: vec![Statement::Assign { line: 2, assignments: vec![("a".to_string(), Value::Iteration(0))] }],
+                },
+                Statement::Expect { line: 3, port: "c".to_string(), value: Value::Literal(1), mask: u64::MAX },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(parse("tick\nrepeat 2 { tick").unwrap_err(), "line 2: missing `}`");
+        assert_eq!(parse("\n\nwait").unwrap_err(), "line 3: expected a number of cycles");
+        assert_eq!(parse("expect a=1").unwrap_err(), "line 1: expected `==`");
+        assert_eq!(parse("a=1 tick").unwrap_err(), "line 1: expected `=` after tick");
+        assert!(parse("}").is_err());
+        assert!(parse("a=$j").is_err());
+    }
+
+    #[test]
+    fn test_compile_afifo() {
+        let io = ports::recipe_io("afifo_test");
+        let program = compile(&parse(AFIFO_TEST).unwrap(), &io).unwrap();
+
+        let frames: Vec<u64> = program.frames.iter().map(|frame| frame.bits()).collect();
+        assert_eq!(
+            frames,
+            vec![0x600, 0x000, 0x200, 0x400, 0xc01, 0xc02, 0xc03, 0x403, 0x403, 0x1403, 0x1403, 0x1403]
+        );
+        assert_eq!(program.expects.len(), 3);
+        assert_eq!(program.expects[2].expected.cycle, 11);
+        assert_eq!(program.expects[2].expected.value, 3);
+        assert_eq!(program.lines[&14], 9..=11);
+
+        assert!(compile(&parse("o_rdata=1").unwrap(), &io).unwrap_err().contains("not an input"));
+        assert!(compile(&parse("expect o_rdata==1").unwrap(), &io).unwrap_err().contains("before the first tick"));
+        assert!(compile(&parse("i_wdata=$i").unwrap(), &io).is_err());
+        assert!(compile(&parse("i_wdata=0x100").unwrap(), &io).is_err());
+    }
+
+    #[test]
+    fn test_check_and_report() {
+        let io = ports::recipe_io("afifo_test");
+        let program = compile(&parse(AFIFO_TEST).unwrap(), &io).unwrap();
+
+        // The FIFO returns 1, 2 and then 2 again
+        let mut outputs = vec![Frame(0); program.frames.len()];
+        outputs[9] = Frame(0x1);
+        outputs[10] = Frame(0x2);
+        outputs[11] = Frame(0x2);
+
+        let results = check(&program, &io, &outputs);
+        assert_eq!(results.iter().filter(|result| result.passed()).count(), 2);
+
+        let rows = report(AFIFO_TEST, &program, &results, &io);
+        let row = rows.iter().find(|row| row.line == 15).unwrap();
+        assert_eq!(row.statement, "expect o_rdata==$i+1");
+        assert_eq!(row.result, "FAIL (2/3), cycle 11: o_rdata expected 3, got 2");
+        assert_eq!(rows.iter().find(|row| row.line == 9).unwrap().result, "ok");
+
+        // Values are reported in the format of the port
+        let mut hex_io = io.clone();
+        ports::find_port_mut(&mut hex_io, "o_rdata").unwrap().format = ports::format::PortFormat::Hex;
+        let rows = report(AFIFO_TEST, &program, &results, &hex_io);
+        let row = rows.iter().find(|row| row.line == 15).unwrap();
+        assert_eq!(row.result, "FAIL (2/3), cycle 11: o_rdata expected 0x03, got 0x02");
+
+        let mismatches = mismatches(&program, &io, &outputs);
+        assert_eq!(mismatches.len(), 1);
+        assert_eq!((mismatches[0].cycle, mismatches[0].differing.as_str()), (11, "0"));
+    }
+
+    #[test]
+    fn test_dont_care() {
+        let io = ports::recipe_io("afifo_test");
+        let program = compile(&parse("tick
+expect o_rdata==0b1x1x
+expect o_rempty==x").unwrap(), &io).unwrap();
+        assert_eq!(program.expects[0].expected.mask, !0b0101);
+
+        // o_rempty (pin 8) is never checked
+        assert!(check(&program, &io, &[Frame(0x10f)]).iter().all(|result| result.passed()));
+        assert!(!check(&program, &io, &[Frame(0x008)])[0].passed());
+    }
+}
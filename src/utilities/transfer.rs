@@ -0,0 +1,197 @@
+/*
+ * Filename: transfer.rs
+ * Desciprtion: VeriComm IO & programming with retries (backoff & a per-session deadline)
+ * and an automatic recovery sequence (io_close, engine_reset, reopen, optional reprogram)
+ */
+
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, Result};
+use owo_colors::OwoColorize;
+
+use crate::device_manager::{BoardSession, TransferSettings};
+use crate::vlfd::{device_handler::DeviceHandler, ProgramHandler};
+
+/// Delay before retry number `attempt` (starting at 1), doubling every attempt.
+pub fn backoff_delay(settings: &TransferSettings, attempt: u32) -> Duration {
+    let factor = 1u64 << attempt.saturating_sub(1).min(10);
+    Duration::from_millis(settings.backoff_ms.saturating_mul(factor))
+}
+
+/// Whether a retry after `delay` still starts within the retry deadline, counted from the first attempt.
+/// Only the retries are bounded, how long a single transfer may take is up to the driver.
+pub fn retry_in_time(settings: &TransferSettings, elapsed: Duration, delay: Duration) -> bool {
+    elapsed + delay <= Duration::from_millis(settings.retry_deadline_ms)
+}
+
+fn log(message: &str) {
+    println!("{} {}", "[usb]".yellow(), message);
+}
+
+/// An open VeriComm IO channel to a mounted board, IO is closed again when dropped.
+pub struct IoSession<'a> {
+    session: &'a BoardSession,
+    handler: DeviceHandler<'a>,
+    /// Bitstream to reprogram the board with during recovery (when enabled in the settings)
+    bitstream: Option<PathBuf>,
+}
+
+impl<'a> IoSession<'a> {
+    /// Opens the device, reads its configuration and opens VeriComm IO.
+    pub fn open(session: &'a BoardSession, bitstream: Option<PathBuf>) -> Result<Self> {
+        let mut io_session = Self { session, handler: DeviceHandler::new(session.handle()), bitstream };
+        io_session.reopen()?;
+        Ok(io_session)
+    }
+
+    fn reopen(&mut self) -> Result<()> {
+        self.handler.open().map_err(|e| anyhow!("{}", e))?;
+        self.handler.init().map_err(|e| anyhow!("{}", e))?;
+        self.handler.io_open().map_err(|e| anyhow!("{}", e))?;
+        self.session.set_io_open(true);
+        Ok(())
+    }
+
+    /// The underlying handler, e.g. to read the configuration space.
+    pub fn handler(&mut self) -> &mut DeviceHandler<'a> {
+        &mut self.handler
+    }
+
+    /// Sends `tx_buffer` and reads `rx_buffer`, retrying with backoff and running the
+    /// recovery sequence once all retries have failed.
+    pub fn write_read(&mut self, tx_buffer: &mut Vec<u16>, rx_buffer: &mut Vec<u16>) -> Result<()> {
+        let settings = self.session.settings();
+
+        let started = Instant::now();
+        let mut last_error = String::new();
+        for attempt in 0..=settings.retries {
+            if attempt > 0 {
+                let delay = backoff_delay(&settings, attempt);
+                if !retry_in_time(&settings, started.elapsed(), delay) {
+                    log(&format!("retry deadline of {} ms reached, no more retries", settings.retry_deadline_ms));
+                    break;
+                }
+                log(&format!("retry {}/{} in {} ms", attempt, settings.retries, delay.as_millis()));
+                thread::sleep(delay);
+            }
+            match self.handler.io_write_read_data(tx_buffer, rx_buffer) {
+                Ok(_) => return Ok(()),
+                Err(e) => {
+                    last_error = e.to_string();
+                    log(&format!("transfer of {} words failed: {}", tx_buffer.len(), last_error));
+                }
+            }
+        }
+
+        if let Err(e) = self.recover() {
+            return Err(anyhow!("transfer failed ({}) and recovery failed: {}", last_error, e));
+        }
+        self.handler
+            .io_write_read_data(tx_buffer, rx_buffer)
+            .map_err(|e| anyhow!("transfer failed after retries and recovery: {}", e))
+    }
+
+    /// io_close, engine_reset, reopen and (optionally) reprogram the board.
+    pub fn recover(&mut self) -> Result<()> {
+        let settings = self.session.settings();
+        log("running recovery sequence");
+
+        log("io_close");
+        if let Err(e) = self.handler.io_close() {
+            log(&format!("io_close failed: {} (continuing)", e));
+        }
+        self.session.set_io_open(false);
+
+        log("engine_reset");
+        self.handler.engine_reset().map_err(|e| anyhow!("engine_reset failed: {}", e))?;
+
+        if settings.reprogram {
+            match self.bitstream.clone() {
+                Some(bitstream) => {
+                    log(&format!("reprogramming {}", bitstream.display()));
+                    program(self.session, &bitstream)?;
+                }
+                None => log("reprogram enabled but no project is loaded, skipping"),
+            }
+        }
+
+        log("reopening");
+        self.reopen()?;
+        log("recovered");
+        Ok(())
+    }
+}
+
+impl Drop for IoSession<'_> {
+    fn drop(&mut self) {
+        if self.session.is_io_open() {
+            let _ = self.handler.io_close();
+            self.session.set_io_open(false);
+        }
+    }
+}
+
+/// Programs a bitstream, retrying with backoff according to the session settings.
+pub fn program(session: &BoardSession, bitstream: &Path) -> Result<()> {
+    let settings = session.settings();
+
+    let started = Instant::now();
+    let mut last_error = String::new();
+    for attempt in 0..=settings.retries {
+        if attempt > 0 {
+            let delay = backoff_delay(&settings, attempt);
+            if !retry_in_time(&settings, started.elapsed(), delay) {
+                log(&format!("retry deadline of {} ms reached, no more retries", settings.retry_deadline_ms));
+                break;
+            }
+            log(&format!("programming retry {}/{} in {} ms", attempt, settings.retries, delay.as_millis()));
+            thread::sleep(delay);
+        }
+
+        let mut program_handler = ProgramHandler::new(session.handle());
+        let result = program_handler.open_device().and_then(|_| program_handler.program(bitstream));
+        let _ = program_handler.close_device();
+
+        match result {
+            Ok(_) => {
+                session.set_programmed(true);
+                return Ok(());
+            }
+            Err(e) => {
+                last_error = e.to_string();
+                log(&format!("programming failed: {}", last_error));
+            }
+        }
+    }
+
+    session.set_programmed(false);
+    Err(anyhow!("programming failed after retries: {}", last_error))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backoff_delay() {
+        let settings = TransferSettings { backoff_ms: 100, ..TransferSettings::default() };
+        assert_eq!(backoff_delay(&settings, 1), Duration::from_millis(100));
+        assert_eq!(backoff_delay(&settings, 2), Duration::from_millis(200));
+        assert_eq!(backoff_delay(&settings, 4), Duration::from_millis(800));
+        // Capped so a large retry count cannot overflow
+        assert_eq!(backoff_delay(&settings, 40), Duration::from_millis(100 * 1024));
+
+        let no_backoff = TransferSettings { backoff_ms: 0, ..TransferSettings::default() };
+        assert_eq!(backoff_delay(&no_backoff, 3), Duration::ZERO);
+    }
+
+    #[test]
+    fn test_retry_in_time() {
+        let settings = TransferSettings { retry_deadline_ms: 1000, ..TransferSettings::default() };
+        assert!(retry_in_time(&settings, Duration::from_millis(100), Duration::from_millis(900)));
+        assert!(!retry_in_time(&settings, Duration::from_millis(900), Duration::from_millis(200)));
+        assert!(!retry_in_time(&settings, Duration::from_millis(1500), Duration::ZERO));
+    }
+}
@@ -0,0 +1,349 @@
+/*
+ * Filename: trigger.rs
+ * Desciprtion: Logic analyzer triggers on the output ports (values with don't care bits, edges,
+ * changes & boolean combinations of them) and the capture of a window of cycles around the trigger:
+ *     o_rdata==0x3x && !o_rempty==1
+ *     rise(o_wfull) || change(o_rdata)
+ */
+
+use std::collections::VecDeque;
+
+use crate::ports::{frame::Frame, IOPort, IOType};
+use super::capture::{Capture, Sample};
+use super::golden;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Edge {
+    Rise,
+    Fall,
+    /// Any change of the value
+    Change,
+}
+
+/// A trigger condition, ports are indices into the IO ports of the project.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Condition {
+    /// The checked bits (`mask`) of the port equal `value`, or differ if `!equal`
+    Value { port: usize, value: u64, mask: u64, equal: bool },
+    Edge { port: usize, edge: Edge },
+    Not(Box<Condition>),
+    And(Box<Condition>, Box<Condition>),
+    Or(Box<Condition>, Box<Condition>),
+}
+
+impl Condition {
+    /// Evaluates the condition with the values of the ports in this cycle & the previous one (no edges in the first cycle).
+    pub fn eval(&self, previous: Option<&[u64]>, current: &[u64]) -> bool {
+        match self {
+            Condition::Value { port, value, mask, equal } => ((current[*port] ^ value) & mask == 0) == *equal,
+            Condition::Edge { port, edge } => previous.is_some_and(|previous| {
+                let (before, now) = (previous[*port], current[*port]);
+                match edge {
+                    Edge::Rise => before & 0x1 == 0 && now & 0x1 == 1,
+                    Edge::Fall => before & 0x1 == 1 && now & 0x1 == 0,
+                    Edge::Change => before != now,
+                }
+            }),
+            Condition::Not(condition) => !condition.eval(previous, current),
+            Condition::And(a, b) => a.eval(previous, current) && b.eval(previous, current),
+            Condition::Or(a, b) => a.eval(previous, current) || b.eval(previous, current),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Word(String),
+    Op(&'static str),
+}
+
+const OPERATORS: [&str; 8] = ["==", "!=", "&&", "||", "!", "(", ")", "="];
+
+fn tokenize(text: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut rest = text.trim_start();
+    while !rest.is_empty() {
+        if let Some(op) = OPERATORS.iter().find(|op| rest.starts_with(**op)) {
+            if *op == "=" {
+                return Err("use == to compare values".to_string());
+            }
+            tokens.push(Token::Op(op));
+            rest = &rest[op.len()..];
+        } else {
+            let end = rest.find(|c: char| c.is_whitespace() || "=!&|()".contains(c)).unwrap_or(rest.len());
+            if end == 0 {
+                return Err(format!("unexpected \"{}\"", &rest[..1]));
+            }
+            tokens.push(Token::Word(rest[..end].to_string()));
+            rest = &rest[end..];
+        }
+        rest = rest.trim_start();
+    }
+    Ok(tokens)
+}
+
+struct Parser<'a> {
+    tokens: Vec<Token>,
+    position: usize,
+    io: &'a [IOPort],
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
+
+    fn expect(&mut self, op: &str) -> Result<(), String> {
+        match self.next() {
+            Some(Token::Op(found)) if found == op => Ok(()),
+            Some(Token::Op(found)) => Err(format!("expected \"{}\", found \"{}\"", op, found)),
+            Some(Token::Word(found)) => Err(format!("expected \"{}\", found \"{}\"", op, found)),
+            None => Err(format!("expected \"{}\" at the end", op)),
+        }
+    }
+
+    fn port(&self, name: &str) -> Result<usize, String> {
+        let index = self
+            .io
+            .iter()
+            .position(|port| port.io_name == name || port.io_name.eq_ignore_ascii_case(name))
+            .ok_or(format!("\"{}\" is not a port of the project", name))?;
+        if !matches!(self.io[index].io_type, IOType::OUTPUT) {
+            return Err(format!("{} is not an output port", self.io[index].io_name));
+        }
+        Ok(index)
+    }
+
+    fn or(&mut self) -> Result<Condition, String> {
+        let mut condition = self.and()?;
+        while self.peek() == Some(&Token::Op("||")) {
+            self.next();
+            condition = Condition::Or(Box::new(condition), Box::new(self.and()?));
+        }
+        Ok(condition)
+    }
+
+    fn and(&mut self) -> Result<Condition, String> {
+        let mut condition = self.unary()?;
+        while self.peek() == Some(&Token::Op("&&")) {
+            self.next();
+            condition = Condition::And(Box::new(condition), Box::new(self.unary()?));
+        }
+        Ok(condition)
+    }
+
+    fn unary(&mut self) -> Result<Condition, String> {
+        match self.next() {
+            Some(Token::Op("!")) => Ok(Condition::Not(Box::new(self.unary()?))),
+            Some(Token::Op("(")) => {
+                let condition = self.or()?;
+                self.expect(")")?;
+                Ok(condition)
+            }
+            Some(Token::Word(word)) if self.peek() == Some(&Token::Op("(")) => {
+                let edge = match word.to_lowercase().as_str() {
+                    "rise" | "rising" => Edge::Rise,
+                    "fall" | "falling" => Edge::Fall,
+                    "change" | "changed" => Edge::Change,
+                    _ => return Err(format!("unknown function \"{}\" (rise, fall or change)", word)),
+                };
+                self.next();
+                let port = match self.next() {
+                    Some(Token::Word(name)) => self.port(&name)?,
+                    _ => return Err(format!("{}() needs a port", word)),
+                };
+                self.expect(")")?;
+                if edge != Edge::Change && self.io[port].width() != 1 {
+                    return Err(format!("{}() needs a single bit port, {} is {} bits", word, self.io[port].io_name, self.io[port].width()));
+                }
+                Ok(Condition::Edge { port, edge })
+            }
+            Some(Token::Word(name)) => {
+                let port = self.port(&name)?;
+                let equal = match self.next() {
+                    Some(Token::Op("==")) => true,
+                    Some(Token::Op("!=")) => false,
+                    _ => return Err(format!("expected == or != after {}", name)),
+                };
+                let (value, mask) = match self.next() {
+                    Some(Token::Word(value)) => golden::parse_expected(&value)?,
+                    _ => return Err(format!("expected a value after {}", name)),
+                };
+                Ok(Condition::Value { port, value, mask, equal })
+            }
+            Some(Token::Op(op)) => Err(format!("unexpected \"{}\"", op)),
+            None => Err("the condition is incomplete".to_string()),
+        }
+    }
+}
+
+/// Parses a trigger condition on the output ports of a project.
+pub fn parse(text: &str, io: &[IOPort]) -> Result<Condition, String> {
+    let mut parser = Parser { tokens: tokenize(text)?, position: 0, io };
+    if parser.tokens.is_empty() {
+        return Err("the condition is empty".to_string());
+    }
+    let condition = parser.or()?;
+    match parser.next() {
+        None => Ok(condition),
+        Some(Token::Word(word)) => Err(format!("unexpected \"{}\"", word)),
+        Some(Token::Op(op)) => Err(format!("unexpected \"{}\"", op)),
+    }
+}
+
+/// Captures a window of `pre` cycles before the trigger, the cycle it fired in & `post` cycles after it.
+#[derive(Debug, Clone)]
+pub struct TriggerCapture {
+    condition: Condition,
+    pre: usize,
+    post: usize,
+    /// Decodes the output frames, a copy of the IO ports of the project
+    io: Vec<IOPort>,
+    previous: Option<Vec<u64>>,
+    /// The last `pre` cycles before the trigger
+    ring: VecDeque<Sample>,
+    window: Vec<Sample>,
+    /// Cycles seen so far & the one the trigger fired in
+    pub cycles: u64,
+    pub fired: Option<u64>,
+}
+
+impl TriggerCapture {
+    pub fn new(condition: Condition, io: &[IOPort], pre: usize, post: usize) -> Self {
+        Self {
+            condition,
+            pre,
+            post,
+            io: io.to_vec(),
+            previous: None,
+            ring: VecDeque::with_capacity(pre),
+            window: Vec::new(),
+            cycles: 0,
+            fired: None,
+        }
+    }
+
+    /// Adds the frames of a cycle, returns whether the window is complete.
+    pub fn push(&mut self, tx: Frame, rx: Frame) -> bool {
+        let sample = Sample { tx, rx };
+        self.cycles += 1;
+        if self.fired.is_some() {
+            self.window.push(sample);
+            return self.done();
+        }
+
+        for port in self.io.iter_mut().filter(|port| matches!(port.io_type, IOType::OUTPUT)) {
+            port.update(rx.bits());
+        }
+        let current: Vec<u64> = self.io.iter().map(|port| port.data).collect();
+        if self.condition.eval(self.previous.as_deref(), &current) {
+            self.fired = Some(self.cycles - 1);
+            self.window.extend(self.ring.drain(..));
+            self.window.push(sample);
+            return self.done();
+        }
+        self.previous = Some(current);
+
+        if self.pre > 0 {
+            if self.ring.len() == self.pre {
+                self.ring.pop_front();
+            }
+            self.ring.push_back(sample);
+        }
+        false
+    }
+
+    pub fn done(&self) -> bool {
+        self.fired.is_some() && self.window.len() >= self.trigger_offset() + 1 + self.post
+    }
+
+    /// Where the trigger is in the window (fewer than `pre` cycles before it if it fired early).
+    fn trigger_offset(&self) -> usize {
+        self.fired.map_or(0, |fired| (fired as usize).min(self.pre))
+    }
+
+    /// The captured window & the cycle of the trigger in it, `None` if the trigger did not fire.
+    pub fn into_capture(self) -> Option<(Capture, usize)> {
+        self.fired?;
+        let offset = self.trigger_offset();
+        let mut capture = Capture::new(&self.io);
+        capture.samples = self.window;
+        Some((capture, offset))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ports;
+
+    #[test]
+    fn test_parse() {
+        let io = ports::recipe_io("afifo_test");
+        let o_rdata = io.iter().position(|port| port.io_name == "o_rdata").unwrap();
+        let o_wfull = io.iter().position(|port| port.io_name == "o_wfull").unwrap();
+
+        assert_eq!(
+            parse("o_rdata==0x3x", &io),
+            Ok(Condition::Value { port: o_rdata, value: 0x30, mask: !0xf, equal: true })
+        );
+        assert_eq!(
+            parse("rise(o_wfull) || !(change(o_rdata) && o_rdata != 2)", &io),
+            Ok(Condition::Or(
+                Box::new(Condition::Edge { port: o_wfull, edge: Edge::Rise }),
+                Box::new(Condition::Not(Box::new(Condition::And(
+                    Box::new(Condition::Edge { port: o_rdata, edge: Edge::Change }),
+                    Box::new(Condition::Value { port: o_rdata, value: 2, mask: u64::MAX, equal: false }),
+                )))),
+            ))
+        );
+
+        assert!(parse("i_wdata==1", &io).unwrap_err().contains("not an output port"));
+        assert!(parse("rise(o_rdata)", &io).unwrap_err().contains("single bit"));
+        assert!(parse("o_rdata=1", &io).is_err());
+        assert!(parse("o_rdata==1 &&", &io).is_err());
+        assert!(parse("(o_rdata==1", &io).is_err());
+        assert!(parse("o_rdata==1 o_wfull==1", &io).is_err());
+        assert!(parse("missing==1", &io).is_err());
+    }
+
+    #[test]
+    fn test_trigger_capture() {
+        let io = ports::recipe_io("afifo_test");
+        // o_rdata is on output pins 0-7, o_wfull on 9
+        let condition = parse("o_rdata==5 && rise(o_wfull)", &io).unwrap();
+        let mut capture = TriggerCapture::new(condition, &io, 2, 1);
+        let outputs = [0x005u64, 0x200, 0x004, 0x005, 0x205, 0x001, 0x002, 0x003];
+
+        let mut done_at = None;
+        for (cycle, &rx) in outputs.iter().enumerate() {
+            if capture.push(Frame(0), Frame(rx)) {
+                done_at = Some(cycle);
+                break;
+            }
+        }
+        // Fires in cycle 4 (o_rdata 5 & o_wfull rising), done one cycle later
+        assert_eq!(capture.fired, Some(4));
+        assert_eq!(done_at, Some(5));
+
+        let (capture, trigger) = capture.into_capture().unwrap();
+        assert_eq!(trigger, 2);
+        let rx: Vec<u64> = capture.samples.iter().map(|sample| sample.rx.0).collect();
+        assert_eq!(rx, vec![0x004, 0x005, 0x205, 0x001]);
+
+        // Fires before `pre` cycles were seen
+        let condition = parse("o_rdata==5", &io).unwrap();
+        let mut early = TriggerCapture::new(condition.clone(), &io, 4, 0);
+        assert!(early.push(Frame(0), Frame(0x005)));
+        assert_eq!(early.into_capture().unwrap().1, 0);
+
+        let never = TriggerCapture::new(condition, &io, 4, 0);
+        assert!(never.into_capture().is_none());
+    }
+}
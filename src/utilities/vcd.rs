@@ -0,0 +1,309 @@
+/*
+ * Filename: vcd.rs
+ * Desciprtion: Value Change Dump (VCD) export of captured IO, e.g. to inspect a run in GTKWave,
+ * and parsing of VCD files produced by simulation (to use as stimulus)
+ */
+
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+use super::capture::Capture;
+
+/// Short VCD identifier of the n-th variable, using the printable ASCII characters `!` to `~`.
+fn identifier(mut index: usize) -> String {
+    let mut id = String::new();
+    loop {
+        id.push((b'!' + (index % 94) as u8) as char);
+        index /= 94;
+        if index == 0 {
+            break;
+        }
+        index -= 1;
+    }
+    id
+}
+
+fn value_change(value: u64, width: usize, id: &str) -> String {
+    if width == 1 {
+        format!("{}{}", value & 0x1, id)
+    } else {
+        format!("b{:b} {}", value, id)
+    }
+}
+
+/// Writes every recorded port as a VCD variable with the declared range of the port, one timestamp per cycle.
+/// The period of the design clock is not known, so the timestamps are cycle numbers rather than a time.
+pub fn write_vcd(capture: &Capture, out: &mut impl Write) -> std::io::Result<()> {
+    let ports = capture.ports();
+    let values: Vec<Vec<u64>> = ports.iter().map(|port| capture.values(port)).collect();
+
+    writeln!(out, "$version fde_cli {} $end", env!("CARGO_PKG_VERSION"))?;
+    writeln!(out, "$comment timestamps are cycle numbers, the period of the design clock is unknown $end")?;
+    writeln!(out, "$scope module top $end")?;
+    for (i, port) in ports.iter().enumerate() {
+        let width = port.width();
+        let range = if width > 1 { format!(" {}", port.range()) } else { String::new() };
+        writeln!(out, "$var wire {} {} {}{} $end", width, identifier(i), port.io_name, range)?;
+    }
+    writeln!(out, "$upscope $end")?;
+    writeln!(out, "$enddefinitions $end")?;
+
+    for cycle in 0..capture.len() {
+        let changes: Vec<String> = ports
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| cycle == 0 || values[*i][cycle] != values[*i][cycle - 1])
+            .map(|(i, port)| value_change(values[i][cycle], port.width(), &identifier(i)))
+            .collect();
+        if changes.is_empty() {
+            continue;
+        }
+
+        writeln!(out, "#{}", cycle)?;
+        if cycle == 0 {
+            writeln!(out, "$dumpvars")?;
+        }
+        for change in changes {
+            writeln!(out, "{}", change)?;
+        }
+        if cycle == 0 {
+            writeln!(out, "$end")?;
+        }
+    }
+    writeln!(out, "#{}", capture.len())?;
+    Ok(())
+}
+
+/// Writes a capture to a `.vcd` file.
+pub fn save_vcd(capture: &Capture, path: &str) -> Result<(), String> {
+    let file = File::create(path).map_err(|e| format!("failed to create {}: {}", path, e))?;
+    let mut out = BufWriter::new(file);
+    write_vcd(capture, &mut out)
+        .and_then(|_| out.flush())
+        .map_err(|e| format!("failed to write {}: {}", path, e))
+}
+
+/// A variable declared in a VCD file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VcdSignal {
+    /// Identifier code the value changes refer to
+    pub id: String,
+    /// Hierarchical name (scopes joined with `.`), e.g. `tb.dut.i_wdata`
+    pub name: String,
+    /// Name of the variable in its scope without the bit range, e.g. `i_wdata`
+    pub reference: String,
+    pub width: usize,
+    /// Set for single bit selects like `i_wdata [3]`
+    pub bit: Option<usize>,
+}
+
+/// A parsed VCD file, value changes are kept in file order as (time, signal index, value).
+#[derive(Debug, Clone, Default)]
+pub struct VcdFile {
+    pub timescale: String,
+    pub signals: Vec<VcdSignal>,
+    pub changes: Vec<(u64, usize, u64)>,
+    /// The unknown (`x`) & high impedance (`z`) bits of every value change, in the order of `changes`
+    pub unknown: Vec<u64>,
+}
+
+/// Parses a binary vector value, unknown (`x`) & high impedance (`z`) bits read as 0.
+/// Returns (value, mask of the unknown bits).
+fn parse_vector(bits: &str) -> Result<(u64, u64), String> {
+    let bits = &bits[bits.len().saturating_sub(64)..];
+    bits.chars().try_fold((0u64, 0u64), |(value, unknown), bit| match bit.to_ascii_lowercase() {
+        '0' => Ok((value << 1, unknown << 1)),
+        '1' => Ok((value << 1 | 1, unknown << 1)),
+        'x' | 'z' => Ok((value << 1, unknown << 1 | 1)),
+        _ => Err(format!("invalid vector value \"{}\"", bits)),
+    })
+}
+
+/// Parses the `[msb:lsb]`/`[bit]` suffix of a variable reference, returns the single bit of a bit select.
+fn parse_bit_select(range: &str) -> Option<usize> {
+    let inner = range.strip_prefix('[')?.strip_suffix(']')?;
+    if inner.contains(':') {
+        return None;
+    }
+    inner.trim().parse().ok()
+}
+
+/// Collects the tokens of a declaration up to its `$end`.
+fn until_end<'a>(tokens: &mut impl Iterator<Item = &'a str>) -> Vec<&'a str> {
+    tokens.by_ref().take_while(|token| *token != "$end").collect()
+}
+
+/// Parses the declarations & value changes of a VCD file.
+pub fn parse_vcd(text: &str) -> Result<VcdFile, String> {
+    let mut vcd = VcdFile::default();
+    let mut ids: std::collections::HashMap<String, usize> = std::collections::HashMap::new();
+    let mut scopes: Vec<String> = Vec::new();
+    let mut time = 0u64;
+    let mut tokens = text.split_whitespace();
+
+    while let Some(token) = tokens.next() {
+        match token {
+            "$timescale" => vcd.timescale = until_end(&mut tokens).join(""),
+            "$scope" => {
+                let scope = until_end(&mut tokens);
+                scopes.push(scope.get(1).unwrap_or(&"").to_string());
+            }
+            "$upscope" => {
+                until_end(&mut tokens);
+                scopes.pop();
+            }
+            "$var" => {
+                let var = until_end(&mut tokens);
+                if var.len() < 4 {
+                    return Err(format!("invalid declaration \"$var {} $end\"", var.join(" ")));
+                }
+                let width: usize = var[1].parse().map_err(|_| format!("invalid width \"{}\" of {}", var[1], var[3]))?;
+                let (reference, range) = match var[3].find('[') {
+                    Some(split) => (&var[3][..split], &var[3][split..]),
+                    None => (var[3], var.get(4).copied().unwrap_or("")),
+                };
+                let mut name = scopes.clone();
+                name.push(reference.to_string());
+
+                ids.insert(var[2].to_string(), vcd.signals.len());
+                vcd.signals.push(VcdSignal {
+                    id: var[2].to_string(),
+                    name: name.join("."),
+                    reference: reference.to_string(),
+                    width,
+                    bit: if width == 1 { parse_bit_select(range) } else { None },
+                });
+            }
+            "$comment" | "$date" | "$version" | "$enddefinitions" => {
+                until_end(&mut tokens);
+            }
+            // Sections of the value changes, the changes in them are handled like any other
+            "$dumpvars" | "$dumpall" | "$dumpon" | "$dumpoff" | "$end" => {}
+            _ if token.starts_with('#') => {
+                time = token[1..].parse().map_err(|_| format!("invalid timestamp \"{}\"", token))?;
+            }
+            _ if token.starts_with(['b', 'B']) => {
+                let (value, unknown) = parse_vector(&token[1..])?;
+                let id = tokens.next().ok_or("vector value without identifier")?;
+                if let Some(&index) = ids.get(id) {
+                    vcd.changes.push((time, index, value));
+                    vcd.unknown.push(unknown);
+                }
+            }
+            _ if token.starts_with(['r', 'R']) => {
+                // Real values cannot drive pins
+                tokens.next();
+            }
+            _ if token.starts_with(['0', '1', 'x', 'X', 'z', 'Z']) => {
+                let value = (token.as_bytes()[0] == b'1') as u64;
+                let unknown = matches!(token.as_bytes()[0], b'x' | b'X' | b'z' | b'Z') as u64;
+                if let Some(&index) = ids.get(&token[1..]) {
+                    vcd.changes.push((time, index, value));
+                    vcd.unknown.push(unknown);
+                }
+            }
+            _ => return Err(format!("unexpected token \"{}\"", token)),
+        }
+    }
+
+    Ok(vcd)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ports::{self, frame::Frame};
+
+    #[test]
+    fn test_identifier() {
+        assert_eq!(identifier(0), "!");
+        assert_eq!(identifier(93), "~");
+        assert_eq!(identifier(94), "!!");
+        assert_ne!(identifier(95), identifier(94));
+    }
+
+    #[test]
+    fn test_write_vcd() {
+        let mut io = ports::recipe_io("afifo_test");
+        let o_rdata = ports::find_port_mut(&mut io, "o_rdata").unwrap();
+        o_rdata.declare(ports::bus::Range { msb: 0, lsb: 7 }).unwrap();
+        let mut capture = Capture::new(&io);
+        capture.push(Frame(0x600), Frame(0x100));
+        capture.push(Frame(0xc03), Frame(0x100));
+        capture.push(Frame(0xc03), Frame(0x003));
+
+        let mut out = Vec::new();
+        write_vcd(&capture, &mut out).unwrap();
+        let vcd = String::from_utf8(out).unwrap();
+
+        // Ports are sorted by name: fifo_1_w_full, i_rd, i_wdata, i_wr, nReset, o_rdata, o_rempty, o_wfull, rst
+        assert!(vcd.contains("$var wire 8 # i_wdata [7:0] $end"), "{}", vcd);
+        assert!(vcd.contains("$var wire 1 ) rst $end"), "{}", vcd);
+        // The declared range is kept, so index 0 stays the MSB in a viewer
+        assert!(vcd.contains("$var wire 8 & o_rdata [0:7] $end"), "{}", vcd);
+        // One timestamp per cycle, the period is unknown so no time unit is claimed
+        assert!(!vcd.contains("$timescale"), "{}", vcd);
+        assert!(vcd.contains("#0\n$dumpvars\n"), "{}", vcd);
+        // Only what changed is dumped, o_rdata[0] & [1] are the two most significant bits of [0:7]
+        assert!(vcd.contains("#1\nb11 #\n1$\n0)\n#2\nb11000000 &\n0'\n#3\n"), "{}", vcd);
+    }
+
+    #[test]
+    fn test_parse_vcd() {
+        let text = "
+            $date today $end
+            $timescale 1 ns $end
+            $scope module tb $end
+            $scope module dut $end
+            $var wire 8 ! i_wdata [7:0] $end
+            $var reg 1 \" nReset $end
+            $var wire 1 # bus[3] $end
+            $var real 1 $ temperature $end
+            $upscope $end
+            $upscope $end
+            $enddefinitions $end
+            #0
+            $dumpvars
+            bx !
+            0\"
+            z#
+            r1.5 $
+            $end
+            #10
+            b101 !
+            1\"
+            #20
+            b1x1 !
+        ";
+        let vcd = parse_vcd(text).unwrap();
+        assert_eq!(vcd.timescale, "1ns");
+        assert_eq!(vcd.signals.len(), 4);
+        assert_eq!(vcd.signals[0].name, "tb.dut.i_wdata");
+        assert_eq!(vcd.signals[0].width, 8);
+        assert_eq!(vcd.signals[2].reference, "bus");
+        assert_eq!(vcd.signals[2].bit, Some(3));
+        assert_eq!(
+            vcd.changes,
+            vec![(0, 0, 0), (0, 1, 0), (0, 2, 0), (10, 0, 0b101), (10, 1, 1), (20, 0, 0b101)]
+        );
+        assert_eq!(vcd.unknown, vec![0x1, 0, 0x1, 0, 0, 0b010]);
+
+        assert!(parse_vcd("#abc").is_err());
+        assert!(parse_vcd("b102 !").is_err());
+    }
+
+    #[test]
+    fn test_export_parses_back() {
+        let mut capture = Capture::new(&ports::recipe_io("afifo_test"));
+        capture.push(Frame(0x600), Frame(0x0));
+        capture.push(Frame(0xc03), Frame(0x0));
+
+        let mut out = Vec::new();
+        write_vcd(&capture, &mut out).unwrap();
+        let vcd = parse_vcd(&String::from_utf8(out).unwrap()).unwrap();
+
+        let wdata = vcd.signals.iter().position(|signal| signal.name == "top.i_wdata").unwrap();
+        assert_eq!(vcd.signals[wdata].width, 8);
+        assert!(vcd.changes.contains(&(1, wdata, 0x3)));
+    }
+}
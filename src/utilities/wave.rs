@@ -0,0 +1,211 @@
+/*
+ * Filename: wave.rs
+ * Desciprtion: Terminal (Unicode) timing diagrams of the last capture, single-bit ports as square
+ * waves and buses as value labeled segments, with horizontal scrolling & zoom
+ */
+
+use crate::ports::IOPort;
+use super::capture::Capture;
+
+pub const DEFAULT_ZOOM: usize = 4;
+pub const MAX_ZOOM: usize = 32;
+
+/// What part of the last capture `wave` shows, kept between commands so the view can be scrolled.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WaveView {
+    /// Ports to show, all recorded ports when empty
+    pub ports: Vec<String>,
+    /// First cycle shown
+    pub from: usize,
+    /// Last cycle shown (inclusive), up to the width of the terminal when `None`
+    pub to: Option<usize>,
+    /// Characters per cycle
+    pub zoom: usize,
+}
+
+impl Default for WaveView {
+    fn default() -> Self {
+        Self { ports: Vec::new(), from: 0, to: None, zoom: DEFAULT_ZOOM }
+    }
+}
+
+impl WaveView {
+    /// Number of cycles that fit in `width` columns next to the port names.
+    pub fn visible_cycles(&self, name_width: usize, width: usize) -> usize {
+        (width.saturating_sub(name_width + 3) / self.zoom.clamp(1, MAX_ZOOM)).max(1)
+    }
+
+    /// Scrolls by `cycles` (negative to the left), staying within the capture.
+    pub fn scroll(&mut self, cycles: isize, len: usize) {
+        // An explicit range keeps its width
+        let span = self.to.map(|to| to.saturating_sub(self.from));
+        let from = self.from as isize + cycles;
+        self.from = from.clamp(0, len.saturating_sub(1) as isize) as usize;
+        self.to = span.map(|span| self.from + span);
+    }
+}
+
+/// Terminal width from `$COLUMNS`, 100 columns if unknown.
+pub fn terminal_width() -> usize {
+    std::env::var("COLUMNS").ok().and_then(|columns| columns.parse().ok()).unwrap_or(100)
+}
+
+fn bit_line(values: &[u64], first: Option<u64>, zoom: usize) -> String {
+    let mut line = String::new();
+    let mut previous = first;
+    for &value in values {
+        let level = if value & 0x1 == 1 { '▔' } else { '▁' };
+        let edge = match (previous, value & 0x1) {
+            (Some(0), 1) => '╱',
+            (Some(1), 0) => '╲',
+            _ => level,
+        };
+        line.push(edge);
+        line.extend(std::iter::repeat_n(level, zoom - 1));
+        previous = Some(value & 0x1);
+    }
+    line
+}
+
+fn bus_line(values: &[u64], first: Option<u64>, zoom: usize, label: impl Fn(u64) -> String) -> String {
+    let mut line: Vec<char> = Vec::with_capacity(values.len() * zoom);
+    let mut start = 0;
+    while start < values.len() {
+        let value = values[start];
+        let end = values[start..].iter().position(|&v| v != value).map_or(values.len(), |len| start + len);
+        let columns = (end - start) * zoom;
+
+        let changed = start > 0 || first.is_some_and(|first| first != value);
+        let mut segment: Vec<char> = if changed { vec!['╳'] } else { vec![' '] };
+        let label: Vec<char> = label(value).chars().collect();
+        if segment.len() + label.len() <= columns {
+            segment.extend(label);
+        }
+        segment.resize(columns, ' ');
+        segment.truncate(columns);
+        line.extend(segment);
+        start = end;
+    }
+    line.into_iter().collect()
+}
+
+/// Cycle numbers above the diagram, as many as fit without overlapping.
+fn axis_line(from: usize, cycles: usize, zoom: usize) -> String {
+    let mut line = String::new();
+    for cycle in from..from + cycles {
+        let column = (cycle - from) * zoom;
+        if line.chars().count() <= column {
+            let padding = column - line.chars().count();
+            line.push_str(&" ".repeat(padding));
+            line.push_str(&cycle.to_string());
+            line.push(' ');
+        }
+    }
+    line.trim_end().to_string()
+}
+
+/// Renders the view of a capture into lines of at most `width` columns.
+pub fn render(capture: &Capture, view: &WaveView, width: usize) -> Result<Vec<String>, String> {
+    if capture.is_empty() {
+        return Err("the capture is empty".to_string());
+    }
+    if view.from >= capture.len() {
+        return Err(format!("cycle {} is past the end of the capture ({} cycles)", view.from, capture.len()));
+    }
+
+    let recorded = capture.ports();
+    let ports: Vec<&IOPort> = if view.ports.is_empty() {
+        recorded
+    } else {
+        view.ports
+            .iter()
+            .map(|name| {
+                recorded
+                    .iter()
+                    .find(|port| port.io_name == *name || port.io_name.eq_ignore_ascii_case(name))
+                    .copied()
+                    .ok_or(format!("\"{}\" is not a port of the capture", name))
+            })
+            .collect::<Result<_, _>>()?
+    };
+
+    let zoom = view.zoom.clamp(1, MAX_ZOOM);
+    let name_width = ports.iter().map(|port| port.io_name.len()).max().unwrap_or(0);
+    let last = view.to.unwrap_or(usize::MAX).min(capture.len() - 1);
+    let cycles = (last + 1).saturating_sub(view.from).min(view.visible_cycles(name_width, width)).max(1);
+    let range = view.from..view.from + cycles;
+
+    let mut lines = vec![format!("{:>name_width$}   {}", "", axis_line(view.from, cycles, zoom))];
+    for port in ports {
+        let values = capture.values(port);
+        let first = view.from.checked_sub(1).map(|previous| values[previous]);
+        let wave = if port.width() == 1 {
+            bit_line(&values[range.clone()], first, zoom)
+        } else {
+            bus_line(&values[range.clone()], first, zoom, |value| port.format.render(value, port.width()))
+        };
+        lines.push(format!("{:>name_width$} │ {}", port.io_name, wave));
+    }
+    Ok(lines)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ports::{self, format::PortFormat, frame::Frame};
+
+    fn afifo_capture() -> Capture {
+        let mut capture = Capture::new(&ports::recipe_io("afifo_test"));
+        // i_wr (pin 11) toggles, i_wdata (pins 0-7) counts 1, 1, 2, 3
+        for tx in [0x001, 0xc01, 0x402, 0xc03] {
+            capture.push(Frame(tx), Frame(0));
+        }
+        capture
+    }
+
+    #[test]
+    fn test_bit_and_bus_lines() {
+        assert_eq!(bit_line(&[0, 1, 1, 0], None, 2), "▁▁╱▔▔▔╲▁");
+        assert_eq!(bit_line(&[1], Some(0), 3), "╱▔▔");
+        let hex = |value: u64| format!("{:x}", value);
+        assert_eq!(bus_line(&[1, 1, 2, 3], None, 2, hex), " 1  ╳2╳3");
+        // Labels that do not fit are left out
+        assert_eq!(bus_line(&[0xabc, 0x1], Some(0x1), 3, hex), "╳  ╳1 ");
+    }
+
+    #[test]
+    fn test_render() {
+        let capture = afifo_capture();
+        let view = WaveView { ports: vec!["i_wr".to_string(), "I_WDATA".to_string()], ..WaveView::default() };
+        let lines = render(&capture, &view, 80).unwrap();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0], "          0   1   2   3");
+        assert_eq!(lines[1], "   i_wr │ ▁▁▁▁╱▔▔▔╲▁▁▁╱▔▔▔");
+        assert_eq!(lines[2], "i_wdata │  1      ╳2  ╳3  ");
+
+        // Bus values in the format of the port
+        let mut capture = capture;
+        ports::find_port_mut(&mut capture.io, "i_wdata").unwrap().format = PortFormat::Signed;
+        capture.push(Frame(0xff), Frame(0));
+        let view = WaveView { ports: vec!["i_wdata".to_string()], ..WaveView::default() };
+        assert!(render(&capture, &view, 80).unwrap()[1].ends_with("╳-1 "));
+
+        // Scrolled & limited to what fits
+        let mut view = WaveView { ports: vec!["i_wr".to_string()], zoom: 1, ..WaveView::default() };
+        view.scroll(2, capture.len());
+        let lines = render(&capture, &view, 9).unwrap();
+        assert_eq!(lines[1], "i_wr │ ╲╱");
+
+        view.ports = vec!["missing".to_string()];
+        assert!(render(&capture, &view, 80).is_err());
+    }
+
+    #[test]
+    fn test_scroll() {
+        let mut view = WaveView { from: 2, to: Some(5), ..WaveView::default() };
+        view.scroll(-10, 8);
+        assert_eq!((view.from, view.to), (0, Some(3)));
+        view.scroll(100, 8);
+        assert_eq!(view.from, 7);
+    }
+}